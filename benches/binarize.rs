@@ -0,0 +1,57 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use image::{DynamicImage, GrayImage};
+use qr_code_generator::binarize;
+use qr_code_generator::config::Config;
+use qr_code_generator::qr_embedder::QrEmbedder;
+
+/// A full-size output: a QR code on a gradient background
+fn sample_image() -> GrayImage {
+    let background = image::RgbImage::from_fn(1920, 1080, |x, y| {
+        image::Rgb([(x % 256) as u8, (y % 256) as u8, ((x + y) % 256) as u8])
+    });
+    QrEmbedder::new(Config::default())
+        .embed_qr_code(DynamicImage::ImageRgb8(background), "https://example.com")
+        .expect("embedding succeeds")
+        .to_luma8()
+}
+
+fn bench_binarisers(c: &mut Criterion) {
+    let image = sample_image();
+    let mut group = c.benchmark_group("binarize_1920x1080");
+    group.sample_size(20);
+
+    group.bench_function("adaptive_mean", |b| {
+        b.iter(|| {
+            let mut gray = image.clone();
+            binarize::adaptive_threshold(
+                black_box(&mut gray),
+                binarize::DEFAULT_WINDOW,
+                binarize::DEFAULT_OFFSET,
+            );
+            gray
+        })
+    });
+    group.bench_function("otsu", |b| {
+        b.iter(|| {
+            let mut gray = image.clone();
+            binarize::otsu_threshold(black_box(&mut gray));
+            gray
+        })
+    });
+    group.bench_function("sauvola", |b| {
+        b.iter(|| {
+            let mut gray = image.clone();
+            binarize::sauvola_threshold(
+                black_box(&mut gray),
+                binarize::DEFAULT_WINDOW,
+                binarize::DEFAULT_SAUVOLA_K,
+            );
+            gray
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_binarisers);
+criterion_main!(benches);
@@ -0,0 +1,151 @@
+use crate::config::Config;
+use crate::error::Result;
+use crate::image_provider::{spawn_blocking, AsyncImageProvider, Background, BackgroundSource, ProviderContext};
+use crate::repair::RepairedImage;
+use crate::QrImageGenerator;
+use image::DynamicImage;
+use std::sync::{Arc, Mutex};
+use tokio::runtime::Handle;
+
+/// Async front end to [`QrImageGenerator`] for callers inside a tokio
+/// runtime. Backgrounds are downloaded with the async HTTP client; embedding
+/// and validation run on the blocking thread pool. Clones share the
+/// generator, HTTP client and background cache.
+#[derive(Clone)]
+pub struct AsyncQrImageGenerator {
+    generator: Arc<QrImageGenerator>,
+    provider: AsyncImageProvider,
+}
+
+impl AsyncQrImageGenerator {
+    pub fn new(config: Config) -> Result<Self> {
+        let context = ProviderContext::new(config.background_cache_size);
+        Self::with_context(config, context)
+    }
+
+    /// Create a generator sharing an HTTP client and background cache with
+    /// other generators
+    pub fn with_context(config: Config, context: ProviderContext) -> Result<Self> {
+        let generator = QrImageGenerator::with_context(config.clone(), context.clone())?;
+        Ok(Self {
+            generator: Arc::new(generator),
+            provider: AsyncImageProvider::with_context(config, context),
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        self.generator.config()
+    }
+
+    /// Async [`QrImageGenerator::generate`]
+    pub async fn generate(&self, keyword: &str, qr_data: &str) -> Result<DynamicImage> {
+        self.generate_bytes(keyword, qr_data.as_bytes()).await
+    }
+
+    /// Async [`QrImageGenerator::generate_bytes`]
+    pub async fn generate_bytes(&self, keyword: &str, qr_data: &[u8]) -> Result<DynamicImage> {
+        self.generate_repaired(keyword, qr_data)
+            .await
+            .map(|repaired| repaired.image)
+    }
+
+    /// Async [`QrImageGenerator::generate_repaired`]
+    pub async fn generate_repaired(&self, keyword: &str, qr_data: &[u8]) -> Result<RepairedImage> {
+        let first = self.provider.fetch_background(keyword).await?;
+        let source = PrefetchedSource {
+            first: Mutex::new(Some(first)),
+            provider: self.provider.clone(),
+            runtime: Handle::current(),
+        };
+        let generator = Arc::clone(&self.generator);
+        let (keyword, qr_data) = (keyword.to_string(), qr_data.to_vec());
+        spawn_blocking(move || generator.generate_repaired_from(&source, &keyword, &qr_data)).await
+    }
+}
+
+/// Background downloaded before generation moves to the blocking pool.
+/// Replacements needed by the repair loop are fetched back on the caller's
+/// runtime.
+struct PrefetchedSource {
+    first: Mutex<Option<Background>>,
+    provider: AsyncImageProvider,
+    runtime: Handle,
+}
+
+impl BackgroundSource for PrefetchedSource {
+    fn background(&self, keyword: &str) -> Result<Background> {
+        let first = self.first.lock().unwrap_or_else(|e| e.into_inner()).take();
+        match first {
+            Some(background) => Ok(background),
+            None => self.runtime.block_on(self.provider.fetch_background(keyword)),
+        }
+    }
+
+    fn new_background(&self, keyword: &str, draw: u32) -> Result<Background> {
+        self.runtime
+            .block_on(self.provider.fetch_replacement_background(keyword, draw))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_provider::BackgroundOrigin;
+    use crate::output::encode_png;
+    use image::{GenericImageView, Rgba, RgbaImage};
+    use tiny_http::{Response, Server};
+
+    /// Serve a random-photo endpoint and the photo it points to
+    fn unsplash_stub() -> (Arc<Server>, String) {
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let base = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let photo = RgbaImage::from_pixel(64, 48, Rgba([200, 200, 200, 255]));
+        let png = encode_png(&DynamicImage::ImageRgba8(photo), None).unwrap();
+        let json = format!(
+            r#"{{"id":"stub","urls":{{"raw":"{}/photo.png?ixid=stub"}},"user":{{"name":"Stub"}}}}"#,
+            base
+        );
+
+        let http = Arc::clone(&server);
+        std::thread::spawn(move || {
+            for request in http.incoming_requests() {
+                let authorized = request.headers().iter().any(|header| {
+                    header.field.equiv("Authorization") && header.value.as_str() == "Client-ID test-key"
+                });
+                let response = if request.url().starts_with("/photos/random") && authorized {
+                    Response::from_data(json.as_bytes().to_vec())
+                } else if request.url().starts_with("/photo.png") {
+                    Response::from_data(png.clone())
+                } else {
+                    Response::from_data(Vec::new()).with_status_code(404)
+                };
+                let _ = request.respond(response);
+            }
+        });
+        (server, base)
+    }
+
+    #[tokio::test]
+    async fn test_fetches_with_async_client() {
+        let (server, base) = unsplash_stub();
+        let config = Config {
+            unsplash_api_key: Some("test-key".to_string()),
+            ..Config::default()
+        }
+        .with_dimensions(480, 360);
+        assert!(AsyncQrImageGenerator::new(config.clone().with_dimensions(0, 0)).is_err());
+
+        let context = ProviderContext::new(config.background_cache_size).with_api_base(&base);
+        let generator = AsyncQrImageGenerator::with_context(config, context).unwrap();
+        let repaired = generator.generate_repaired("sea", b"hello").await.unwrap();
+        assert_eq!(repaired.background.origin, BackgroundOrigin::Unsplash);
+        assert_eq!(
+            repaired.background.attribution.as_deref(),
+            Some("Photo by Stub on Unsplash")
+        );
+        assert_eq!((repaired.image.width(), repaired.image.height()), (480, 360));
+        assert_eq!(repaired.image.get_pixel(5, 5), Rgba([200, 200, 200, 255]));
+
+        server.unblock();
+    }
+}
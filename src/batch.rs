@@ -0,0 +1,360 @@
+use crate::config::{self, Config};
+use crate::error::{QrImageError, Result};
+use crate::image_provider::ProviderContext;
+use crate::output;
+use crate::provenance::Provenance;
+use crate::qr_validator::ValidationReport;
+use crate::repair::Adjustment;
+use crate::request::GenerationRequest;
+use crate::QrImageGenerator;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+/// Manifest columns every row needs; any other column overrides the config
+/// field of the same name, and `preset` applies a named preset first
+pub const REQUIRED_COLUMNS: [&str; 3] = ["keyword", "data", "output"];
+
+/// Backgrounds a batch run keeps at least, so rows sharing a keyword and
+/// size download their background once
+pub const CACHE_SIZE: usize = 16;
+
+/// One code to generate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchItem {
+    pub keyword: String,
+    pub data: String,
+    /// Where the image is saved, relative to the working directory
+    pub output: PathBuf,
+    /// Config fields for this row only, e.g. `qr_position: "Center"`
+    #[serde(flatten)]
+    pub overrides: serde_json::Map<String, serde_json::Value>,
+}
+
+impl BatchItem {
+    /// The base config with this row's preset and overrides applied
+    pub fn config(&self, base: &Config) -> Result<Config> {
+        base.clone().with_preset_and_overrides(self.overrides.clone())
+    }
+}
+
+/// Read a CSV (header row required) or JSONL manifest, chosen by extension
+pub fn read_manifest(path: &Path) -> Result<Vec<BatchItem>> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let contents = std::fs::read_to_string(path)?;
+    let invalid = |line: usize, e: &dyn std::fmt::Display| {
+        QrImageError::ConfigError(format!("{}:{}: {}", path.display(), line, e))
+    };
+
+    match extension.as_str() {
+        "csv" => {
+            let mut reader = csv::Reader::from_reader(contents.as_bytes());
+            let headers = reader.headers().map_err(|e| invalid(1, &e))?.clone();
+            if let Some(missing) = REQUIRED_COLUMNS
+                .iter()
+                .find(|column| !headers.iter().any(|h| h == **column))
+            {
+                return Err(invalid(1, &format!("missing column '{}'", missing)));
+            }
+
+            reader
+                .records()
+                .enumerate()
+                .map(|(index, record)| {
+                    let record = record.map_err(|e| invalid(index + 2, &e))?;
+                    let mut row = serde_json::Map::new();
+                    for (column, cell) in headers.iter().zip(record.iter()) {
+                        let value = if REQUIRED_COLUMNS.contains(&column) || column == "preset" {
+                            serde_json::Value::String(cell.to_string())
+                        } else if cell.is_empty() {
+                            continue;
+                        } else {
+                            config::parse_override(cell)
+                        };
+                        row.insert(column.to_string(), value);
+                    }
+                    serde_json::from_value(row.into()).map_err(|e| invalid(index + 2, &e))
+                })
+                .collect()
+        }
+        "jsonl" | "ndjson" => contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| serde_json::from_str(line).map_err(|e| invalid(index + 1, &e)))
+            .collect(),
+        other => Err(QrImageError::ConfigError(format!(
+            "{}: unsupported manifest format '{}' (use csv or jsonl)",
+            path.display(),
+            other
+        ))),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchStatus {
+    Succeeded,
+    Failed,
+}
+
+/// Outcome of one manifest row
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchRow {
+    /// 1-based position in the manifest
+    pub row: usize,
+    pub keyword: String,
+    pub output: PathBuf,
+    pub status: BatchStatus,
+    /// Why the row failed
+    pub error: Option<String>,
+    pub duration_ms: f64,
+    /// Repairs needed to get the code past validation
+    pub adjustments: Vec<Adjustment>,
+    pub report: Option<ValidationReport>,
+}
+
+/// Per-row results and totals of a batch run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub duration_ms: f64,
+    pub rows: Vec<BatchRow>,
+}
+
+impl BatchSummary {
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Generate every item on `base.batch_workers` threads sharing one HTTP
+/// client and background cache, carrying on past failures. `on_row` is called
+/// on the calling thread as each row finishes, in completion order; the
+/// summary lists rows in manifest order.
+pub fn run<F>(base: &Config, items: &[BatchItem], on_row: F) -> BatchSummary
+where
+    F: FnMut(&BatchRow),
+{
+    let context = ProviderContext::new(base.background_cache_size.max(CACHE_SIZE));
+    run_with_context(base, &context, items, on_row)
+}
+
+/// [`run`] with a caller-supplied HTTP client and background cache
+pub(crate) fn run_with_context<F>(
+    base: &Config,
+    context: &ProviderContext,
+    items: &[BatchItem],
+    mut on_row: F,
+) -> BatchSummary
+where
+    F: FnMut(&BatchRow),
+{
+    let started = Instant::now();
+    let workers = base.batch_workers.clamp(1, items.len().max(1));
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    info!("Generating {} item(s) on {} worker(s)", items.len(), workers);
+
+    let mut rows: Vec<BatchRow> = thread::scope(|scope| {
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let next = &next;
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                info!("Batch row {}/{}: {}", index + 1, items.len(), item.output.display());
+                if sender.send(run_item(base, context, index, item)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        receiver.iter().inspect(|row| on_row(row)).collect()
+    });
+    rows.sort_by_key(|row| row.row);
+
+    let succeeded = rows
+        .iter()
+        .filter(|row| row.status == BatchStatus::Succeeded)
+        .count();
+    BatchSummary {
+        total: rows.len(),
+        succeeded,
+        failed: rows.len() - succeeded,
+        duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+        rows,
+    }
+}
+
+fn run_item(base: &Config, context: &ProviderContext, index: usize, item: &BatchItem) -> BatchRow {
+    let started = Instant::now();
+    let result = generate_item(base, context, item);
+    let duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    match result {
+        Ok((adjustments, report)) => BatchRow {
+            row: index + 1,
+            keyword: item.keyword.clone(),
+            output: item.output.clone(),
+            status: BatchStatus::Succeeded,
+            error: None,
+            duration_ms,
+            adjustments,
+            report,
+        },
+        Err(e) => {
+            warn!("Batch row {} failed: {}", index + 1, e);
+            BatchRow {
+                row: index + 1,
+                keyword: item.keyword.clone(),
+                output: item.output.clone(),
+                status: BatchStatus::Failed,
+                error: Some(e.to_string()),
+                duration_ms,
+                adjustments: Vec::new(),
+                report: None,
+            }
+        }
+    }
+}
+
+fn generate_item(
+    base: &Config,
+    context: &ProviderContext,
+    item: &BatchItem,
+) -> Result<(Vec<Adjustment>, Option<ValidationReport>)> {
+    let config = item.config(base)?;
+    let generator = QrImageGenerator::with_context(config, context.clone())?;
+    let result =
+        generator.generate_request(&GenerationRequest::new(&item.keyword, item.data.as_bytes()))?;
+
+    if let Some(parent) = item.output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    output::save_with_metadata(&result.image, &item.output, result.config.dpi, result.embedded_metadata())?;
+    if result.config.provenance {
+        Provenance::from_result(&result, item.data.as_bytes()).write_sidecar(&item.output)?;
+    }
+    Ok((result.adjustments, result.report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::QrPosition;
+    use crate::image_provider::{BackgroundOrigin, ImageProvider};
+
+    #[test]
+    fn test_manifests_with_overrides() {
+        let dir = std::env::temp_dir().join(format!("qrgen-batch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let csv_path = dir.join("codes.csv");
+        std::fs::write(
+            &csv_path,
+            "keyword,data,output,qr_position,image_width\n\
+             sea,https://a.example,a.png,Center,800\n\
+             forest,\"x,y\",b.png,,\n",
+        )
+        .unwrap();
+        let items = read_manifest(&csv_path).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].data, "x,y");
+        assert!(items[1].overrides.is_empty());
+        let config = items[0].config(&Config::default()).unwrap();
+        assert_eq!(config.qr_position, QrPosition::Center);
+        assert_eq!(config.image_width, 800);
+
+        let jsonl_path = dir.join("codes.jsonl");
+        std::fs::write(
+            &jsonl_path,
+            "{\"keyword\":\"sea\",\"data\":\"1\",\"output\":\"c.png\",\"preset\":\"slide\",\"qr_size_ratio\":0.2}\n\n\
+             {\"keyword\":\"sky\",\"data\":\"2\",\"output\":\"d.png\",\"no_such_field\":1}\n",
+        )
+        .unwrap();
+        let items = read_manifest(&jsonl_path).unwrap();
+        let config = items[0].config(&Config::default()).unwrap();
+        assert_eq!((config.image_width, config.qr_size_ratio), (1920, 0.2));
+        assert!(items[1].config(&Config::default()).is_err());
+
+        std::fs::write(&csv_path, "keyword,output\nsea,a.png\n").unwrap();
+        assert!(read_manifest(&csv_path).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_failed_rows_do_not_stop_the_batch() {
+        let item = |output: &str, width: u32| BatchItem {
+            keyword: "sea".to_string(),
+            data: "x".to_string(),
+            output: PathBuf::from(output),
+            overrides: [("image_width".to_string(), width.into())].into_iter().collect(),
+        };
+        let items: Vec<BatchItem> = (0..5).map(|i| item(&format!("zero-{}.png", i), 0)).collect();
+
+        let mut seen = 0;
+        let config = Config::default().with_batch_workers(3);
+        let summary = run(&config, &items, |_| seen += 1);
+        assert_eq!(seen, 5);
+        assert_eq!((summary.total, summary.failed), (5, 5));
+        assert!(summary.rows.iter().enumerate().all(|(i, row)| row.row == i + 1));
+        assert!(summary.rows[0].error.as_deref().unwrap().contains("image dimensions"));
+    }
+
+    #[test]
+    fn test_rows_share_one_background() {
+        let dir = std::env::temp_dir().join(format!("qrgen-batch-run-{}", std::process::id()));
+        let backgrounds = dir.join("backgrounds");
+        std::fs::create_dir_all(&backgrounds).unwrap();
+        // Without a tile behind it the code is unreadable on black, so every
+        // row needs one repair
+        image::RgbImage::new(640, 480).save(backgrounds.join("sea.png")).unwrap();
+        let config = Config {
+            qr_background_opacity: 0,
+            ..Config::default()
+        }
+        .with_seed(7)
+        .with_dimensions(640, 480)
+        .with_background_dir(&backgrounds)
+        .with_batch_workers(2);
+        let items: Vec<BatchItem> = (0..3)
+            .map(|i| BatchItem {
+                keyword: "sea".to_string(),
+                data: format!("https://example.com/{}", i),
+                output: dir.join(format!("out/{}.png", i)),
+                overrides: serde_json::Map::new(),
+            })
+            .collect();
+
+        let context = ProviderContext::new(CACHE_SIZE);
+        let summary = run_with_context(&config, &context, &items, |_| {});
+        assert_eq!((summary.total, summary.succeeded), (3, 3));
+        for (row, item) in summary.rows.iter().zip(&items) {
+            assert!(item.output.exists());
+            assert_eq!(row.adjustments, vec![Adjustment::Opacity(25)]);
+            assert!(row.report.as_ref().is_some_and(|report| report.version > 0));
+        }
+
+        // The rows' background stays cached after its file is gone
+        std::fs::remove_dir_all(&backgrounds).unwrap();
+        let provider = ImageProvider::with_context(config, context);
+        let (_, info) = provider.fetch_background("sea").unwrap();
+        assert_eq!(info.origin, BackgroundOrigin::Local);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
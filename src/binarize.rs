@@ -0,0 +1,195 @@
+use image::GrayImage;
+
+/// Default local window for adaptive thresholding, in pixels
+pub const DEFAULT_WINDOW: u32 = 15;
+
+/// Default constant subtracted from the local mean
+pub const DEFAULT_OFFSET: i32 = 10;
+
+/// Default Sauvola sensitivity
+pub const DEFAULT_SAUVOLA_K: f32 = 0.2;
+
+/// Summed-area tables of pixel values and squared pixel values, so the sum
+/// over any rectangle costs four lookups
+pub struct IntegralImage {
+    width: usize,
+    height: usize,
+    sums: Vec<u64>,
+    squares: Vec<u64>,
+}
+
+impl IntegralImage {
+    pub fn new(image: &GrayImage) -> Self {
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let stride = width + 1;
+        let mut sums = vec![0u64; stride * (height + 1)];
+        let mut squares = vec![0u64; stride * (height + 1)];
+
+        for y in 0..height {
+            let mut row_sum = 0u64;
+            let mut row_squares = 0u64;
+            for x in 0..width {
+                let value = image.get_pixel(x as u32, y as u32)[0] as u64;
+                row_sum += value;
+                row_squares += value * value;
+                sums[(y + 1) * stride + x + 1] = sums[y * stride + x + 1] + row_sum;
+                squares[(y + 1) * stride + x + 1] = squares[y * stride + x + 1] + row_squares;
+            }
+        }
+
+        Self {
+            width,
+            height,
+            sums,
+            squares,
+        }
+    }
+
+    /// Pixel count, sum and sum of squares over the window of `window` pixels
+    /// centred on (x, y), clipped to the image
+    fn window_stats(&self, x: u32, y: u32, window: u32) -> (u64, u64, u64) {
+        let half = window / 2;
+        let x0 = x.saturating_sub(half) as usize;
+        let y0 = y.saturating_sub(half) as usize;
+        let x1 = ((x + half + 1) as usize).min(self.width);
+        let y1 = ((y + half + 1) as usize).min(self.height);
+
+        let stride = self.width + 1;
+        let area = |table: &[u64]| {
+            table[y1 * stride + x1] + table[y0 * stride + x0]
+                - table[y0 * stride + x1]
+                - table[y1 * stride + x0]
+        };
+
+        (
+            ((x1 - x0) * (y1 - y0)) as u64,
+            area(&self.sums),
+            area(&self.squares),
+        )
+    }
+}
+
+/// Mean-C adaptive threshold: a pixel is white when it is brighter than the
+/// mean of the centred `window`×`window` neighbourhood minus `offset`
+pub fn adaptive_threshold(image: &mut GrayImage, window: u32, offset: i32) {
+    let integral = IntegralImage::new(image);
+    let window = window.max(1);
+
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let (count, sum, _) = integral.window_stats(x, y, window);
+        let threshold = (sum / count) as i32 - offset;
+        pixel[0] = if pixel[0] as i32 > threshold { 255 } else { 0 };
+    }
+}
+
+/// Global threshold maximising the between-class variance of the histogram
+pub fn otsu_level(image: &GrayImage) -> u8 {
+    let mut histogram = [0u64; 256];
+    for pixel in image.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+
+    let total = image.pixels().len() as f64;
+    let weighted_total: f64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(level, &count)| level as f64 * count as f64)
+        .sum();
+
+    let mut best_level = 0u8;
+    let mut best_variance = 0.0;
+    let mut background_weight = 0.0;
+    let mut background_sum = 0.0;
+
+    for (level, &count) in histogram.iter().enumerate() {
+        background_weight += count as f64;
+        if background_weight == 0.0 {
+            continue;
+        }
+        let foreground_weight = total - background_weight;
+        if foreground_weight == 0.0 {
+            break;
+        }
+
+        background_sum += level as f64 * count as f64;
+        let background_mean = background_sum / background_weight;
+        let foreground_mean = (weighted_total - background_sum) / foreground_weight;
+        let variance = background_weight
+            * foreground_weight
+            * (background_mean - foreground_mean).powi(2);
+
+        if variance > best_variance {
+            best_variance = variance;
+            best_level = level as u8;
+        }
+    }
+
+    best_level
+}
+
+/// Binarise with Otsu's global threshold
+pub fn otsu_threshold(image: &mut GrayImage) {
+    let level = otsu_level(image);
+    for pixel in image.pixels_mut() {
+        pixel[0] = if pixel[0] > level { 255 } else { 0 };
+    }
+}
+
+/// Sauvola local threshold `mean * (1 + k * (std_dev / 128 - 1))`, which
+/// copes better than mean-C with uneven lighting and textured backgrounds
+pub fn sauvola_threshold(image: &mut GrayImage, window: u32, k: f32) {
+    let integral = IntegralImage::new(image);
+    let window = window.max(1);
+
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let (count, sum, squares) = integral.window_stats(x, y, window);
+        let count = count as f64;
+        let mean = sum as f64 / count;
+        let variance = (squares as f64 / count - mean * mean).max(0.0);
+        let threshold = mean * (1.0 + k as f64 * (variance.sqrt() / 128.0 - 1.0));
+        pixel[0] = if pixel[0] as f64 > threshold { 255 } else { 0 };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    #[test]
+    fn test_window_is_centred() {
+        // A single dark pixel only darkens windows that actually contain it
+        let mut image = GrayImage::from_pixel(9, 9, Luma([200]));
+        image.put_pixel(4, 4, Luma([0]));
+        let integral = IntegralImage::new(&image);
+
+        let (count, sum, _) = integral.window_stats(4, 4, 3);
+        assert_eq!((count, sum), (9, 8 * 200));
+        let (count, sum, _) = integral.window_stats(0, 0, 3);
+        assert_eq!((count, sum), (4, 4 * 200));
+        let (_, sum, _) = integral.window_stats(6, 4, 3);
+        assert_eq!(sum, 9 * 200);
+    }
+
+    #[test]
+    fn test_binarisers_separate_two_levels() {
+        let mut image = GrayImage::from_fn(40, 40, |x, _| Luma([if x < 20 { 40 } else { 210 }]));
+        let level = otsu_level(&image);
+        assert!((40..210).contains(&level));
+
+        let binarisers: [fn(&mut GrayImage); 3] = [
+            |img| adaptive_threshold(img, DEFAULT_WINDOW, DEFAULT_OFFSET),
+            otsu_threshold,
+            |img| sauvola_threshold(img, DEFAULT_WINDOW, DEFAULT_SAUVOLA_K),
+        ];
+        for binarise in binarisers {
+            let mut result = image.clone();
+            binarise(&mut result);
+            assert_eq!(result.get_pixel(19, 5)[0], 0);
+            assert_eq!(result.get_pixel(20, 5)[0], 255);
+        }
+
+        otsu_threshold(&mut image);
+        assert!(image.pixels().all(|p| p[0] == 0 || p[0] == 255));
+    }
+}
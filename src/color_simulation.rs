@@ -0,0 +1,213 @@
+use image::{DynamicImage, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Paper white of an uncoated stock, in sRGB
+const PAPER_WHITE: [f32; 3] = [245.0, 243.0, 236.0];
+
+/// Densest black the simulated press reaches, in sRGB
+const PRINT_BLACK: [f32; 3] = [35.0, 33.0, 38.0];
+
+/// Tone value increase at 50% coverage on uncoated paper
+const DOT_GAIN: f32 = 0.15;
+
+/// A way the code's colours may be altered before anyone scans it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorSimulation {
+    /// Missing L cones (red-blind)
+    Protanopia,
+    /// Missing M cones (green-blind)
+    Deuteranopia,
+    /// Missing S cones (blue-blind)
+    Tritanopia,
+    /// Printed on a greyscale printer
+    Grayscale,
+    /// Converted to CMYK and printed on uncoated paper
+    Cmyk,
+}
+
+impl ColorSimulation {
+    /// Every simulation, in the order they are checked
+    pub fn all() -> Vec<ColorSimulation> {
+        vec![
+            ColorSimulation::Protanopia,
+            ColorSimulation::Deuteranopia,
+            ColorSimulation::Tritanopia,
+            ColorSimulation::Grayscale,
+            ColorSimulation::Cmyk,
+        ]
+    }
+
+    /// Apply the simulation to every pixel
+    pub fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let mut rgb = image.to_rgb8();
+        match self {
+            ColorSimulation::Grayscale => {
+                let gray = DynamicImage::ImageRgb8(rgb).to_luma8();
+                return DynamicImage::ImageRgb8(DynamicImage::ImageLuma8(gray).to_rgb8());
+            }
+            ColorSimulation::Protanopia
+            | ColorSimulation::Deuteranopia
+            | ColorSimulation::Tritanopia => {
+                let matrix = self.cvd_matrix();
+                map_pixels(&mut rgb, |color| {
+                    let linear = color.map(to_linear);
+                    let mut simulated = [0.0; 3];
+                    for (out, row) in simulated.iter_mut().zip(&matrix) {
+                        *out = row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2];
+                    }
+                    simulated.map(to_srgb)
+                });
+            }
+            ColorSimulation::Cmyk => map_pixels(&mut rgb, print_cmyk),
+        }
+        DynamicImage::ImageRgb8(rgb)
+    }
+
+    /// Machado, Oliveira and Fernandes (2009) matrices at full severity, for
+    /// linear RGB
+    fn cvd_matrix(&self) -> [[f32; 3]; 3] {
+        match self {
+            ColorSimulation::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            ColorSimulation::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            _ => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+}
+
+impl fmt::Display for ColorSimulation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorSimulation::Protanopia => write!(f, "protanopia"),
+            ColorSimulation::Deuteranopia => write!(f, "deuteranopia"),
+            ColorSimulation::Tritanopia => write!(f, "tritanopia"),
+            ColorSimulation::Grayscale => write!(f, "grayscale print"),
+            ColorSimulation::Cmyk => write!(f, "CMYK print"),
+        }
+    }
+}
+
+/// Result of decoding after one colour simulation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColorCheck {
+    pub simulation: ColorSimulation,
+    pub passed: bool,
+    /// Why decoding failed, when it did
+    pub detail: Option<String>,
+}
+
+fn map_pixels<F>(image: &mut RgbImage, convert: F)
+where
+    F: Fn([f32; 3]) -> [f32; 3],
+{
+    for pixel in image.pixels_mut() {
+        let converted = convert(pixel.0.map(|c| c as f32));
+        *pixel = Rgb(converted.map(|c| c.round().clamp(0.0, 255.0) as u8));
+    }
+}
+
+/// Naive RGB to CMYK separation, then ink coverage with dot gain laid on
+/// paper: the paper caps the white point and the inks cannot reach pure black
+fn print_cmyk(color: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = color.map(|c| c / 255.0);
+    let k = 1.0 - r.max(g).max(b);
+    let separate = |c: f32| {
+        if k >= 1.0 {
+            0.0
+        } else {
+            (1.0 - c - k) / (1.0 - k)
+        }
+    };
+    let inks = [separate(r), separate(g), separate(b)].map(dot_gain);
+    let k = dot_gain(k);
+
+    let mut printed = [0.0; 3];
+    for channel in 0..3 {
+        let reflectance = (1.0 - inks[channel]) * (1.0 - k);
+        printed[channel] =
+            PRINT_BLACK[channel] + (PAPER_WHITE[channel] - PRINT_BLACK[channel]) * reflectance;
+    }
+    printed
+}
+
+/// Tone value increase peaking at mid-tones
+fn dot_gain(coverage: f32) -> f32 {
+    (coverage + DOT_GAIN * 4.0 * coverage * (1.0 - coverage)).clamp(0.0, 1.0)
+}
+
+fn to_linear(channel: f32) -> f32 {
+    let c = channel / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn to_srgb(linear: f32) -> f32 {
+    let c = linear.clamp(0.0, 1.0);
+    let encoded = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    encoded * 255.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simulate(simulation: ColorSimulation, color: [u8; 3]) -> [u8; 3] {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb(color)));
+        simulation.apply(&image).to_rgb8().get_pixel(0, 0).0
+    }
+
+    #[test]
+    fn test_neutral_colours_survive_cvd() {
+        for simulation in [
+            ColorSimulation::Protanopia,
+            ColorSimulation::Deuteranopia,
+            ColorSimulation::Tritanopia,
+        ] {
+            assert_eq!(simulate(simulation, [0, 0, 0]), [0, 0, 0]);
+            let white = simulate(simulation, [255, 255, 255]);
+            assert!(white.iter().all(|&c| c >= 250), "{} {:?}", simulation, white);
+        }
+    }
+
+    #[test]
+    fn test_red_green_confusion() {
+        // Red and green of similar lightness become near-identical for a
+        // deuteranope
+        let red = simulate(ColorSimulation::Deuteranopia, [200, 80, 0]);
+        let green = simulate(ColorSimulation::Deuteranopia, [110, 140, 0]);
+        let distance: i32 = red
+            .iter()
+            .zip(&green)
+            .map(|(&a, &b)| (a as i32 - b as i32).abs())
+            .sum();
+        assert!(distance < 40, "{:?} vs {:?}", red, green);
+    }
+
+    #[test]
+    fn test_cmyk_print_compresses_range() {
+        let black = simulate(ColorSimulation::Cmyk, [0, 0, 0]);
+        let white = simulate(ColorSimulation::Cmyk, [255, 255, 255]);
+        assert_eq!(black, [35, 33, 38]);
+        assert_eq!(white, [245, 243, 236]);
+        assert_eq!(simulate(ColorSimulation::Grayscale, [255, 0, 0])[0], 54);
+    }
+}
@@ -1,7 +1,24 @@
+use crate::binarize;
+use crate::color_simulation::ColorSimulation;
+use crate::error::{QrImageError, Result};
+use crate::preprocess::PreprocessStep;
+use crate::preset::Preset;
+use crate::qr_validator::DecoderBackend;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
+use std::path::{Path, PathBuf};
 
+/// Config file looked for in the working directory
+pub const LOCAL_CONFIG_FILE: &str = "qrgen.toml";
+
+/// Prefix of environment variables overriding config fields, e.g.
+/// `QRGEN_IMAGE_WIDTH=1280`
+pub const ENV_PREFIX: &str = "QRGEN_";
+
+/// Generator settings. Fields missing from a config file keep their defaults.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     /// Unsplash API access key (get from https://unsplash.com/developers)
     pub unsplash_api_key: Option<String>,
@@ -18,11 +35,100 @@ pub struct Config {
     /// QR code position: TopLeft, TopRight, BottomLeft, BottomRight, Center
     pub qr_position: QrPosition,
 
-    /// Maximum validation attempts
+    /// Print resolution recorded in saved PNG and JPEG files
+    pub dpi: Option<u32>,
+
+    /// User-defined presets, selectable by name alongside the built-in ones
+    pub presets: BTreeMap<String, Preset>,
+
+    /// Makes background picks reproducible: local-directory and Unsplash
+    /// search picks and placeholder colours follow the seed, and the random
+    /// fallback photo is skipped
+    pub seed: Option<u64>,
+
+    /// Unsplash photo id to use instead of searching, e.g. the `photo_id`
+    /// recorded in a provenance sidecar; needs an API key
+    pub background_photo: Option<String>,
+
+    /// Directory of images to pick backgrounds from before trying Unsplash;
+    /// files whose name contains the keyword are preferred
+    pub background_dir: Option<PathBuf>,
+
+    /// Maximum validation attempts. Caps `preprocessing`: only its first
+    /// `max_validation_attempts` strategies are tried. Defaults to 5 (3 before
+    /// Otsu and Sauvola were added) so every default strategy gets a try.
     pub max_validation_attempts: u32,
 
+    /// Window size in pixels for adaptive and Sauvola thresholding
+    pub threshold_window: u32,
+
+    /// Constant subtracted from the local mean by adaptive thresholding
+    pub threshold_offset: i32,
+
+    /// Preprocessing strategies tried in order, one per validation attempt
+    pub preprocessing: Vec<PreprocessStep>,
+
     /// QR code background opacity (0-255)
     pub qr_background_opacity: u8,
+
+    /// Colour of dark modules (sRGB)
+    pub dark_color: [u8; 3],
+
+    /// Colour of light modules and the tile behind the code (sRGB)
+    pub light_color: [u8; 3],
+
+    /// Decoders cross-checked after rqrr validates the code
+    pub decoders: Vec<DecoderBackend>,
+
+    /// Fail validation unless every decoder reads the code
+    pub require_decoder_agreement: bool,
+
+    /// Keep running the remaining preprocessing strategies after the code is
+    /// read, to catch stray codes only a later strategy sees. Codes decoded
+    /// together with ours are always checked; this costs up to one decode per
+    /// remaining strategy on every successful validation
+    pub detect_stray_codes: bool,
+
+    /// Colour-vision and print simulations decoded after validation; their
+    /// outcomes are recorded in the validation report
+    pub color_simulations: Vec<ColorSimulation>,
+
+    /// QR code error correction level: Low, Medium, Quartile, High
+    pub error_correction: ErrorCorrection,
+
+    /// Symbol type: Qr or MicroQr
+    pub code_type: CodeType,
+
+    /// Largest QR version (1-40) used for each symbol when a payload too long
+    /// for one code is split with Structured Append; lower values give more,
+    /// smaller codes
+    pub max_symbol_version: u8,
+
+    /// Minimum robustness score (0-100) a generated code must reach under
+    /// simulated degradations; 0 disables the check
+    pub min_robustness_score: u8,
+
+    /// Extra attempts with escalating adjustments (opacity, size, error
+    /// correction, position, background) when validation fails
+    pub repair_budget: u32,
+
+    /// Backgrounds kept in memory per keyword and size, so repeated
+    /// generations reuse a photo instead of fetching a new one; 0 (the
+    /// default) disables the cache. Batch runs keep at least
+    /// [`batch::CACHE_SIZE`](crate::batch::CACHE_SIZE).
+    pub background_cache_size: usize,
+
+    /// Rows generated concurrently by a batch run
+    pub batch_workers: usize,
+
+    /// Write a `<file name>.provenance.json` sidecar next to each saved
+    /// image recording how it was made
+    pub provenance: bool,
+
+    /// Embed the payload, generator version and photo credit in saved
+    /// images: PNG text chunks, XMP for JPEG and WebP. Off by default, since
+    /// anyone holding the file can then read the payload without scanning.
+    pub embed_metadata: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -34,6 +140,58 @@ pub enum QrPosition {
     Center,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ErrorCorrection {
+    Low,
+    Medium,
+    Quartile,
+    High,
+}
+
+/// Symbol type to generate.
+///
+/// Rectangular Micro QR (rMQR) is not offered: the `qrcode` encoder has no
+/// support for it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum CodeType {
+    /// Standard QR code, versions 1-40
+    Qr,
+    /// Micro QR code, versions M1-M4 (single finder pattern, 2-module quiet zone)
+    MicroQr,
+}
+
+impl CodeType {
+    /// Symbol versions available for this code type, smallest first
+    pub fn versions(self) -> Vec<qrcode::Version> {
+        match self {
+            CodeType::Qr => (1..=40).map(qrcode::Version::Normal).collect(),
+            CodeType::MicroQr => (1..=4).map(qrcode::Version::Micro).collect(),
+        }
+    }
+}
+
+impl From<ErrorCorrection> for qrcode::EcLevel {
+    fn from(level: ErrorCorrection) -> Self {
+        match level {
+            ErrorCorrection::Low => qrcode::EcLevel::L,
+            ErrorCorrection::Medium => qrcode::EcLevel::M,
+            ErrorCorrection::Quartile => qrcode::EcLevel::Q,
+            ErrorCorrection::High => qrcode::EcLevel::H,
+        }
+    }
+}
+
+impl From<qrcode::EcLevel> for ErrorCorrection {
+    fn from(level: qrcode::EcLevel) -> Self {
+        match level {
+            qrcode::EcLevel::L => ErrorCorrection::Low,
+            qrcode::EcLevel::M => ErrorCorrection::Medium,
+            qrcode::EcLevel::Q => ErrorCorrection::Quartile,
+            qrcode::EcLevel::H => ErrorCorrection::High,
+        }
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -42,8 +200,31 @@ impl Default for Config {
             image_height: 1080,
             qr_size_ratio: 0.25,
             qr_position: QrPosition::BottomRight,
-            max_validation_attempts: 3,
+            dpi: None,
+            presets: BTreeMap::new(),
+            max_validation_attempts: 5,
+            threshold_window: binarize::DEFAULT_WINDOW,
+            threshold_offset: binarize::DEFAULT_OFFSET,
+            preprocessing: PreprocessStep::default_pipeline(),
             qr_background_opacity: 230,
+            dark_color: [0, 0, 0],
+            light_color: [255, 255, 255],
+            color_simulations: ColorSimulation::all(),
+            decoders: vec![DecoderBackend::Rqrr],
+            require_decoder_agreement: false,
+            detect_stray_codes: false,
+            error_correction: ErrorCorrection::Medium,
+            code_type: CodeType::Qr,
+            max_symbol_version: 40,
+            min_robustness_score: 0,
+            repair_budget: 5,
+            background_cache_size: 0,
+            batch_workers: 4,
+            provenance: false,
+            embed_metadata: false,
+            seed: None,
+            background_photo: None,
+            background_dir: None,
         }
     }
 }
@@ -53,6 +234,240 @@ impl Config {
         Self::default()
     }
 
+    /// Load a TOML, JSON or YAML config file, chosen by extension
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| QrImageError::ConfigError(format!("{}: {}", path.display(), e)))?;
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("toml")
+            .to_lowercase();
+        let invalid =
+            |e: &dyn std::fmt::Display| QrImageError::ConfigError(format!("{}: {}", path.display(), e));
+
+        match extension.as_str() {
+            "toml" => toml::from_str(&contents).map_err(|e| invalid(&e)),
+            "json" => serde_json::from_str(&contents).map_err(|e| invalid(&e)),
+            "yaml" | "yml" => serde_yaml::from_str(&contents).map_err(|e| invalid(&e)),
+            other => Err(QrImageError::ConfigError(format!(
+                "{}: unsupported config format '{}' (use toml, json or yaml)",
+                path.display(),
+                other
+            ))),
+        }
+    }
+
+    /// The first config file found: `./qrgen.toml`, then
+    /// `$XDG_CONFIG_HOME/qrgen/config.toml` (`~/.config` when unset)
+    pub fn discover() -> Option<PathBuf> {
+        let user_dir = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+        std::iter::once(PathBuf::from(LOCAL_CONFIG_FILE))
+            .chain(user_dir.map(|dir| dir.join("qrgen").join("config.toml")))
+            .find(|path| path.is_file())
+    }
+
+    /// Defaults, overlaid with the given config file (or a discovered one),
+    /// then with environment variables
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = path.map(Path::to_path_buf).or_else(Self::discover);
+        let config = match &path {
+            Some(path) => {
+                log::debug!("Loading config from {}", path.display());
+                Self::from_file(path)?
+            }
+            None => Self::default(),
+        };
+        config.with_env_overrides(env::vars())
+    }
+
+    /// Override fields from `QRGEN_<FIELD>` variables (field names upper-cased)
+    /// and the API key from `UNSPLASH_API_KEY`. Values are parsed as JSON,
+    /// falling back to a plain string, so `QRGEN_QR_POSITION=Center` and
+    /// `QRGEN_DECODERS='["Rqrr","Rxing"]'` both work.
+    pub fn with_env_overrides<I>(self, vars: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let fields = serde_json::to_value(&self)?;
+        let mut overrides = Vec::new();
+
+        for (name, raw) in vars {
+            let field = if name == "UNSPLASH_API_KEY" {
+                "unsplash_api_key".to_string()
+            } else if let Some(field) = name.strip_prefix(ENV_PREFIX) {
+                field.to_lowercase()
+            } else {
+                continue;
+            };
+            if fields.get(&field).is_none() {
+                log::warn!("Ignoring {}: no config field '{}'", name, field);
+                continue;
+            }
+            let parsed = if field == "unsplash_api_key" {
+                serde_json::Value::String(raw)
+            } else {
+                parse_override(&raw)
+            };
+            overrides.push((field, parsed));
+        }
+
+        self.with_overrides(overrides).map_err(|e| {
+            QrImageError::ConfigError(format!("Invalid environment override: {}", e))
+        })
+    }
+
+    /// Replace fields by name, e.g. `("image_width", 1280)`; unknown field
+    /// names and values of the wrong type are errors
+    pub fn with_overrides<I>(self, overrides: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (String, serde_json::Value)>,
+    {
+        let mut value = serde_json::to_value(&self)?;
+        let fields = value
+            .as_object_mut()
+            .expect("Config serializes to an object");
+
+        for (field, override_value) in overrides {
+            if !fields.contains_key(&field) {
+                return Err(QrImageError::ConfigError(format!(
+                    "no config field '{}'",
+                    field
+                )));
+            }
+            fields.insert(field, override_value);
+        }
+
+        serde_json::from_value(value).map_err(|e| QrImageError::ConfigError(e.to_string()))
+    }
+
+    /// Apply a `preset` entry, if any, then override the remaining fields by
+    /// name, as a batch manifest row or service request does
+    pub fn with_preset_and_overrides(
+        self,
+        mut overrides: serde_json::Map<String, serde_json::Value>,
+    ) -> Result<Self> {
+        let mut config = self;
+        if let Some(name) = overrides.remove("preset") {
+            let name = name.as_str().unwrap_or_default().to_string();
+            let preset = Preset::find(&config, &name)
+                .ok_or_else(|| QrImageError::ConfigError(format!("Unknown preset '{}'", name)))?;
+            config = config.with_preset(&preset);
+        }
+        config.with_overrides(overrides)
+    }
+
+    /// Check every setting and report all problems at once, so a bad config
+    /// file can be fixed in one pass
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        if self.image_width == 0 || self.image_height == 0 {
+            problems.push(format!(
+                "image dimensions must be non-zero, got {}×{}",
+                self.image_width, self.image_height
+            ));
+        }
+        if !(0.1..=0.5).contains(&self.qr_size_ratio) {
+            problems.push(format!(
+                "qr_size_ratio must be between 0.1 and 0.5, got {}",
+                self.qr_size_ratio
+            ));
+        }
+        if self.dpi == Some(0) {
+            problems.push("dpi must be greater than 0".to_string());
+        }
+        if self.max_validation_attempts == 0 {
+            problems.push(
+                "max_validation_attempts must be at least 1, or no validation is performed"
+                    .to_string(),
+            );
+        }
+        if self.preprocessing.is_empty() {
+            problems.push("preprocessing needs at least one step, e.g. [\"Identity\"]".to_string());
+        }
+        if self.dark_color == self.light_color {
+            problems.push(format!(
+                "dark_color and light_color are both {:?}; the code would be invisible",
+                self.dark_color
+            ));
+        }
+        if self.code_type == CodeType::MicroQr && self.error_correction == ErrorCorrection::High {
+            problems.push(
+                "error_correction High is not available for MicroQr; use Quartile or lower"
+                    .to_string(),
+            );
+        }
+        if !(1..=40).contains(&self.max_symbol_version) {
+            problems.push(format!(
+                "max_symbol_version must be between 1 and 40, got {}",
+                self.max_symbol_version
+            ));
+        }
+        if self.min_robustness_score > 100 {
+            problems.push(format!(
+                "min_robustness_score must be at most 100, got {}",
+                self.min_robustness_score
+            ));
+        }
+        if self.batch_workers == 0 {
+            problems.push("batch_workers must be at least 1".to_string());
+        }
+        if let Some(id) = &self.background_photo {
+            if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                problems.push(format!("background_photo '{}' is not an Unsplash photo id", id));
+            }
+        }
+        if let Some(dir) = &self.background_dir {
+            if !dir.is_dir() {
+                problems.push(format!("background_dir {} is not a directory", dir.display()));
+            }
+        }
+        if self.require_decoder_agreement && self.decoders.len() < 2 {
+            problems.push(
+                "require_decoder_agreement needs at least two decoders, e.g. [\"Rqrr\", \"Rxing\"]"
+                    .to_string(),
+            );
+        }
+        for backend in &self.decoders {
+            if let Err(e) = backend.create() {
+                problems.push(format!("decoders: {}", e));
+            }
+        }
+        for (name, preset) in &self.presets {
+            if preset.width == 0 || preset.height == 0 {
+                problems.push(format!("preset '{}' has zero dimensions", name));
+            }
+            if !(0.1..=0.5).contains(&preset.qr_size_ratio) {
+                problems.push(format!(
+                    "preset '{}' qr_size_ratio must be between 0.1 and 0.5, got {}",
+                    name, preset.qr_size_ratio
+                ));
+            }
+            if preset.dpi == 0 {
+                problems.push(format!("preset '{}' dpi must be greater than 0", name));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(QrImageError::ConfigError(format!(
+                "{} problem(s) found:\n  - {}",
+                problems.len(),
+                problems.join("\n  - ")
+            )))
+        }
+    }
+
+    /// Serialize as TOML, the format config files are usually written in
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string_pretty(self).map_err(|e| QrImageError::ConfigError(e.to_string()))
+    }
+
     pub fn with_api_key(mut self, key: String) -> Self {
         self.unsplash_api_key = Some(key);
         self
@@ -65,7 +480,7 @@ impl Config {
     }
 
     pub fn with_qr_size_ratio(mut self, ratio: f32) -> Self {
-        self.qr_size_ratio = ratio.clamp(0.1, 0.5);
+        self.qr_size_ratio = ratio;
         self
     }
 
@@ -73,4 +488,223 @@ impl Config {
         self.qr_position = position;
         self
     }
+
+    pub fn with_colors(mut self, dark: [u8; 3], light: [u8; 3]) -> Self {
+        self.dark_color = dark;
+        self.light_color = light;
+        self
+    }
+
+    pub fn with_color_simulations(mut self, simulations: Vec<ColorSimulation>) -> Self {
+        self.color_simulations = simulations;
+        self
+    }
+
+    /// Set the decoders cross-checked after validation, optionally requiring
+    /// all of them to read the code
+    pub fn with_decoders(mut self, decoders: Vec<DecoderBackend>, require_agreement: bool) -> Self {
+        self.decoders = decoders;
+        self.require_decoder_agreement = require_agreement;
+        self
+    }
+
+    /// Take dimensions, code size and position and DPI from a preset
+    pub fn with_preset(mut self, preset: &Preset) -> Self {
+        self.image_width = preset.width;
+        self.image_height = preset.height;
+        self.qr_size_ratio = preset.qr_size_ratio;
+        self.qr_position = preset.position.clone();
+        self.dpi = Some(preset.dpi);
+        self
+    }
+
+    pub fn with_dpi(mut self, dpi: u32) -> Self {
+        self.dpi = Some(dpi);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Use this Unsplash photo instead of searching
+    pub fn with_background_photo(mut self, id: &str) -> Self {
+        self.background_photo = Some(id.to_string());
+        self
+    }
+
+    /// Pick backgrounds from image files in `dir`
+    pub fn with_background_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.background_dir = Some(dir.into());
+        self
+    }
+
+    pub fn with_error_correction(mut self, level: ErrorCorrection) -> Self {
+        self.error_correction = level;
+        self
+    }
+
+    pub fn with_code_type(mut self, code_type: CodeType) -> Self {
+        self.code_type = code_type;
+        self
+    }
+
+    /// Set the threshold window and offset, retuning the adaptive and
+    /// Sauvola steps already in the preprocessing list
+    pub fn with_threshold(mut self, window: u32, offset: i32) -> Self {
+        self.threshold_window = window.max(3);
+        self.threshold_offset = offset;
+        self.preprocessing = self
+            .preprocessing
+            .into_iter()
+            .map(|step| step.with_threshold(self.threshold_window, offset))
+            .collect();
+        self
+    }
+
+    /// Set the preprocessing strategies. Only the first
+    /// `max_validation_attempts` are tried.
+    pub fn with_preprocessing(mut self, steps: Vec<PreprocessStep>) -> Self {
+        self.preprocessing = steps;
+        self
+    }
+
+    pub fn with_max_symbol_version(mut self, version: u8) -> Self {
+        self.max_symbol_version = version;
+        self
+    }
+
+    pub fn with_min_robustness_score(mut self, score: u8) -> Self {
+        self.min_robustness_score = score;
+        self
+    }
+
+    pub fn with_repair_budget(mut self, budget: u32) -> Self {
+        self.repair_budget = budget;
+        self
+    }
+
+    pub fn with_background_cache_size(mut self, size: usize) -> Self {
+        self.background_cache_size = size;
+        self
+    }
+
+    pub fn with_batch_workers(mut self, workers: usize) -> Self {
+        self.batch_workers = workers;
+        self
+    }
+
+    pub fn with_provenance(mut self, enabled: bool) -> Self {
+        self.provenance = enabled;
+        self
+    }
+
+    pub fn with_embed_metadata(mut self, enabled: bool) -> Self {
+        self.embed_metadata = enabled;
+        self
+    }
+}
+
+/// Parse a textual override as JSON, falling back to a plain string so enum
+/// variants and paths need no quoting
+pub fn parse_override(raw: &str) -> serde_json::Value {
+    serde_json::from_str(raw).unwrap_or_else(|_| serde_json::Value::String(raw.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_files_keep_defaults() {
+        let dir = std::env::temp_dir().join(format!("qrgen-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let toml_path = dir.join("config.toml");
+        std::fs::write(&toml_path, "image_width = 1280\nqr_position = \"Center\"\n").unwrap();
+        let config = Config::from_file(&toml_path).unwrap();
+        assert_eq!(config.image_width, 1280);
+        assert_eq!(config.qr_position, QrPosition::Center);
+        assert_eq!(config.image_height, 1080);
+
+        let json_path = dir.join("config.json");
+        std::fs::write(&json_path, r#"{"error_correction": "High"}"#).unwrap();
+        let config = Config::from_file(&json_path).unwrap();
+        assert_eq!(config.error_correction, ErrorCorrection::High);
+
+        let yaml_path = dir.join("config.yaml");
+        std::fs::write(&yaml_path, "repair_budget: 2\npreprocessing:\n  - Identity\n  - Otsu\n").unwrap();
+        let config = Config::from_file(&yaml_path).unwrap();
+        assert_eq!(config.repair_budget, 2);
+        assert_eq!(config.preprocessing, vec![PreprocessStep::Identity, PreprocessStep::Otsu]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let vars = [
+            ("QRGEN_IMAGE_WIDTH", "640"),
+            ("QRGEN_QR_POSITION", "TopLeft"),
+            ("UNSPLASH_API_KEY", "abc"),
+            ("PATH", "/usr/bin"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+
+        let config = Config::default().with_env_overrides(vars).unwrap();
+        assert_eq!(config.image_width, 640);
+        assert_eq!(config.qr_position, QrPosition::TopLeft);
+        assert_eq!(config.unsplash_api_key.as_deref(), Some("abc"));
+
+        let bad = [("QRGEN_IMAGE_WIDTH".to_string(), "wide".to_string())];
+        assert!(Config::default().with_env_overrides(bad).is_err());
+    }
+
+    #[test]
+    fn test_toml_round_trip() {
+        let config = Config::default().with_preprocessing(vec![
+            PreprocessStep::Identity,
+            PreprocessStep::Downscale(0.5),
+        ]);
+        let parsed: Config = toml::from_str(&config.to_toml().unwrap()).unwrap();
+        assert_eq!(parsed.preprocessing, config.preprocessing);
+        assert_eq!(parsed.max_validation_attempts, 5);
+
+        let tuned = Config::default().with_threshold(31, 5);
+        assert!(tuned
+            .preprocessing
+            .contains(&PreprocessStep::AdaptiveThreshold { window: 31, offset: 5 }));
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        assert!(Config::default().validate().is_ok());
+
+        let config = Config {
+            image_width: 0,
+            max_validation_attempts: 0,
+            ..Config::default()
+        }
+        .with_qr_size_ratio(2.0);
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("3 problem(s)"), "{}", message);
+        assert!(message.contains("image dimensions"));
+        assert!(message.contains("qr_size_ratio must be between 0.1 and 0.5, got 2"));
+        assert!(message.contains("max_validation_attempts"));
+
+        let micro = Config::default()
+            .with_code_type(CodeType::MicroQr)
+            .with_error_correction(ErrorCorrection::High);
+        assert!(micro.validate().is_err());
+
+        let long = Config::default().with_preprocessing(vec![PreprocessStep::Identity; 6]);
+        assert!(long.validate().is_ok());
+
+        let lone = Config::default().with_decoders(vec![DecoderBackend::Rqrr], true);
+        assert!(lone.validate().unwrap_err().to_string().contains("at least two decoders"));
+
+        let pinned = Config::default().with_background_photo("../me");
+        assert!(pinned.validate().unwrap_err().to_string().contains("background_photo"));
+    }
 }
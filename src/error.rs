@@ -20,6 +20,9 @@ pub enum QrImageError {
     #[error("Configuration error: {0}")]
     ConfigError(String),
 
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
@@ -29,8 +32,20 @@ pub enum QrImageError {
     #[error("QR code not readable after embedding")]
     QrNotReadable,
 
+    #[error("Found {count} unexpected QR code(s) besides the generated one")]
+    UnexpectedQrCode { count: usize },
+
+    #[error("Robustness score {score} is below the required minimum {required}")]
+    RobustnessTooLow { score: u8, required: u8 },
+
+    #[error("Data encoding error: {0}")]
+    EncodingError(String),
+
     #[error("API error: {0}")]
     ApiError(String),
+
+    #[error("Background task failed: {0}")]
+    TaskError(String),
 }
 
 pub type Result<T> = std::result::Result<T, QrImageError>;
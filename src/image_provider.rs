@@ -1,50 +1,370 @@
 use crate::config::Config;
 use crate::error::{QrImageError, Result};
+use crate::rng::SplitMix64;
 use image::{DynamicImage, ImageFormat};
 use log::{debug, info, warn};
-use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-const UNSPLASH_API_URL: &str = "https://api.unsplash.com/photos/random";
+const UNSPLASH_API_BASE: &str = "https://api.unsplash.com";
+const UNSPLASH_RANDOM_PATH: &str = "/photos/random";
+const UNSPLASH_SEARCH_PATH: &str = "/search/photos";
+const UNSPLASH_PHOTOS_PATH: &str = "/photos";
+/// Search results a seeded run picks from
+const UNSPLASH_SEARCH_PAGE_SIZE: &str = "30";
 const FALLBACK_IMAGE_URL: &str = "https://source.unsplash.com/random";
+const USER_AGENT: &str = "QR-Image-Generator/1.0";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Deserialize)]
 struct UnsplashResponse {
+    id: Option<String>,
     urls: UnsplashUrls,
     description: Option<String>,
     alt_description: Option<String>,
+    user: Option<UnsplashUser>,
+    links: Option<UnsplashLinks>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UnsplashSearchResponse {
+    results: Vec<UnsplashResponse>,
 }
 
 #[derive(Debug, Deserialize)]
 struct UnsplashUrls {
     raw: String,
-    full: String,
-    regular: String,
-    small: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct UnsplashUser {
+    name: String,
+    links: Option<UnsplashLinks>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UnsplashLinks {
+    html: String,
+}
+
+/// Where a background came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackgroundOrigin {
+    /// Unsplash API search for the keyword, or the pinned `background_photo`
+    Unsplash,
+    /// Image file from `background_dir`
+    Local,
+    /// Public random-photo endpoint, used without an API key or when the API fails
+    Fallback,
+    /// Gradient generated locally when no photo could be downloaded
+    Placeholder,
+    /// Image passed in by the caller
+    Supplied,
+}
+
+/// What is known about the background behind a generated code
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackgroundInfo {
+    pub origin: BackgroundOrigin,
+    pub keyword: Option<String>,
+    /// Where the photo was downloaded from, or the local file's path
+    pub url: Option<String>,
+    /// Unsplash photo id; set `background_photo` to it to reuse this photo
+    pub photo_id: Option<String>,
+    /// Photo description or alt text from Unsplash
+    pub description: Option<String>,
+    /// Credit Unsplash asks for wherever the photo is shown, e.g.
+    /// "Photo by Jane Doe (https://unsplash.com/@jane) on Unsplash"
+    pub attribution: Option<String>,
+    /// Unsplash page of the photo
+    pub photo_page: Option<String>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl BackgroundInfo {
+    fn new(origin: BackgroundOrigin, keyword: &str, image: &DynamicImage) -> Self {
+        Self {
+            origin,
+            keyword: Some(keyword.to_string()).filter(|k| !k.is_empty()),
+            url: None,
+            photo_id: None,
+            description: None,
+            attribution: None,
+            photo_page: None,
+            width: image.width(),
+            height: image.height(),
+        }
+    }
+
+    fn with_url(mut self, url: String) -> Self {
+        self.url = Some(url);
+        self
+    }
+
+    /// Describe an image passed in by the caller
+    pub fn supplied(image: &DynamicImage) -> Self {
+        Self::new(BackgroundOrigin::Supplied, "", image)
+    }
+}
+
+/// A background and where it came from
+pub type Background = (DynamicImage, BackgroundInfo);
+
+/// Where the generator gets backgrounds: the first for a keyword may come
+/// from a cache, a repair asking for a new one always gets a fresh image.
+/// `draw` counts replacements from 1, so a seeded run picks the same ones.
+pub(crate) trait BackgroundSource {
+    fn background(&self, keyword: &str) -> Result<Background>;
+    fn new_background(&self, keyword: &str, draw: u32) -> Result<Background>;
+}
+
+/// Keyword, target dimensions and background settings of a cached background
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    keyword: String,
+    width: u32,
+    height: u32,
+    seed: Option<u64>,
+    photo: Option<String>,
+    dir: Option<PathBuf>,
+}
+
+fn cache_key(keyword: &str, config: &Config) -> CacheKey {
+    CacheKey {
+        keyword: keyword.to_string(),
+        width: config.image_width,
+        height: config.image_height,
+        seed: config.seed,
+        photo: config.background_photo.clone(),
+        dir: config.background_dir.clone(),
+    }
+}
+
+/// Most recently used backgrounds, least recently used evicted first
+struct BackgroundCache {
+    capacity: usize,
+    entries: VecDeque<(CacheKey, Background)>,
+}
+
+impl BackgroundCache {
+    fn shared(capacity: usize) -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(Self {
+            capacity,
+            entries: VecDeque::new(),
+        }))
+    }
+
+    fn get(&mut self, key: &CacheKey) -> Option<Background> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        let entry = self.entries.remove(index)?;
+        let background = entry.1.clone();
+        self.entries.push_back(entry);
+        Some(background)
+    }
+
+    fn insert(&mut self, key: CacheKey, background: Background) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.entries.retain(|(k, _)| *k != key);
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((key, background));
+    }
+}
+
+/// Lock a cache; a panic while holding the lock cannot leave it inconsistent
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// HTTP client, background cache and the runtime the blocking API runs
+/// fetches on. Clones share all three, so providers built from one context
+/// reuse connections and downloads across threads.
+///
+/// The client and runtime are created on first use.
+#[derive(Clone)]
+pub struct ProviderContext {
+    client: Arc<OnceLock<reqwest::Client>>,
+    runtime: Arc<OnceLock<BlockingRuntime>>,
+    cache: Arc<Mutex<BackgroundCache>>,
+    /// One lock per background being downloaded, so concurrent requests for
+    /// the same keyword wait for the first download instead of repeating it
+    in_flight: Arc<Mutex<HashMap<CacheKey, Arc<tokio::sync::Mutex<()>>>>>,
+    api_base: Arc<str>,
+}
+
+impl ProviderContext {
+    /// Keep up to `cache_capacity` backgrounds; 0 disables caching
+    pub fn new(cache_capacity: usize) -> Self {
+        Self {
+            client: Arc::new(OnceLock::new()),
+            runtime: Arc::new(OnceLock::new()),
+            cache: BackgroundCache::shared(cache_capacity),
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+            api_base: UNSPLASH_API_BASE.into(),
+        }
+    }
+
+    /// Send Unsplash API requests to a local stand-in
+    #[cfg(all(test, feature = "async"))]
+    pub(crate) fn with_api_base(mut self, base: &str) -> Self {
+        self.api_base = base.into();
+        self
+    }
+
+    fn client(&self) -> &reqwest::Client {
+        self.client.get_or_init(|| {
+            reqwest::Client::builder()
+                .user_agent(USER_AGENT)
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("Failed to create HTTP client")
+        })
+    }
+
+    fn cache(&self) -> std::sync::MutexGuard<'_, BackgroundCache> {
+        lock(&self.cache)
+    }
+
+    fn api_url(&self, path: &str) -> String {
+        format!("{}{}", self.api_base, path)
+    }
+
+    /// Run a fetch to completion on this context's runtime, for the blocking API
+    fn block_on<F: Future>(&self, fetch: F) -> F::Output {
+        self.runtime.get_or_init(BlockingRuntime::new).block_on(fetch)
+    }
+}
+
+/// Runtime behind the blocking API. It is shut down without waiting for its
+/// worker, so the last context may be dropped inside an async runtime.
+struct BlockingRuntime(Option<tokio::runtime::Runtime>);
+
+impl BlockingRuntime {
+    fn new() -> Self {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("qrgen-http")
+            .enable_all()
+            .build()
+            .expect("Failed to create HTTP runtime");
+        Self(Some(runtime))
+    }
+
+    fn block_on<F: Future>(&self, fetch: F) -> F::Output {
+        self.0.as_ref().expect("runtime is only taken on drop").block_on(fetch)
+    }
+}
+
+impl Drop for BlockingRuntime {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.0.take() {
+            runtime.shutdown_background();
+        }
+    }
 }
 
 pub struct ImageProvider {
     config: Config,
-    client: reqwest::blocking::Client,
+    context: ProviderContext,
 }
 
 impl ImageProvider {
     pub fn new(config: Config) -> Self {
-        let client = reqwest::blocking::Client::builder()
-            .user_agent("QR-Image-Generator/1.0")
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .expect("Failed to create HTTP client");
+        let context = ProviderContext::new(config.background_cache_size);
+        Self::with_context(config, context)
+    }
 
-        Self { config, client }
+    /// Create a provider sharing another's HTTP client and background cache
+    pub fn with_context(config: Config, context: ProviderContext) -> Self {
+        Self { config, context }
     }
 
-    /// Fetch an image based on a keyword
+    /// Fetch an image based on a keyword, reusing a cached background of the
+    /// same keyword and size when there is one
     pub fn fetch_image(&self, keyword: &str) -> Result<DynamicImage> {
+        self.fetch_background(keyword).map(|(image, _)| image)
+    }
+
+    /// Fetch a fresh image based on a keyword, bypassing the cache
+    pub fn fetch_new_image(&self, keyword: &str) -> Result<DynamicImage> {
+        self.fetch_new_background(keyword).map(|(image, _)| image)
+    }
+
+    /// [`fetch_image`](Self::fetch_image) along with where the image came from
+    pub fn fetch_background(&self, keyword: &str) -> Result<Background> {
+        self.context.block_on(self.load_background(keyword))
+    }
+
+    /// [`fetch_new_image`](Self::fetch_new_image) along with where the image
+    /// came from
+    pub fn fetch_new_background(&self, keyword: &str) -> Result<Background> {
+        self.fetch_replacement_background(keyword, 0)
+    }
+
+    /// The `draw`-th replacement for a keyword's background. With a seed the
+    /// same draw always picks the same background; draw 0 is the one
+    /// [`fetch_new_background`](Self::fetch_new_background) returns.
+    pub fn fetch_replacement_background(&self, keyword: &str, draw: u32) -> Result<Background> {
+        self.context.block_on(self.load_replacement(keyword, draw))
+    }
+
+    async fn load_background(&self, keyword: &str) -> Result<Background> {
+        if self.context.cache().capacity == 0 {
+            return self.load_replacement(keyword, 0).await;
+        }
+
+        let key = cache_key(keyword, &self.config);
+        if let Some(background) = self.cached(&key) {
+            debug!("Using cached background for keyword: {}", keyword);
+            return Ok(background);
+        }
+
+        let download = Arc::clone(lock(&self.context.in_flight).entry(key.clone()).or_default());
+        let _downloading = download.lock().await;
+        if let Some(background) = self.cached(&key) {
+            debug!("Using background downloaded concurrently for keyword: {}", keyword);
+            return Ok(background);
+        }
+
+        let result = self.load_replacement(keyword, 0).await;
+        if let Ok(background) = &result {
+            self.context.cache().insert(key.clone(), background.clone());
+        }
+        lock(&self.context.in_flight).remove(&key);
+        result
+    }
+
+    fn cached(&self, key: &CacheKey) -> Option<Background> {
+        self.context.cache().get(key)
+    }
+
+    async fn load_replacement(&self, keyword: &str, draw: u32) -> Result<Background> {
         info!("Fetching image for keyword: {}", keyword);
 
+        if let Some(id) = &self.config.background_photo {
+            return self.fetch_pinned_photo(id, keyword, draw).await;
+        }
+
+        if self.config.background_dir.is_some() {
+            let (keyword, config) = (keyword.to_string(), self.config.clone());
+            match spawn_blocking(move || local_background(&keyword, &config, draw)).await {
+                Ok(background) => return Ok(background),
+                Err(e) => warn!("No local background: {}, trying Unsplash", e),
+            }
+        }
+
         // Try Unsplash API first if key is available
         if let Some(api_key) = &self.config.unsplash_api_key {
-            match self.fetch_from_unsplash(keyword, api_key) {
+            match self.fetch_from_unsplash(keyword, api_key, draw).await {
                 Ok(img) => {
                     info!("Successfully fetched image from Unsplash");
                     return Ok(img);
@@ -58,22 +378,50 @@ impl ImageProvider {
         }
 
         // Fallback to public Unsplash source
-        self.fetch_fallback_image(keyword)
+        self.fetch_fallback_image(keyword, draw).await
     }
 
-    fn fetch_from_unsplash(&self, keyword: &str, api_key: &str) -> Result<DynamicImage> {
-        debug!("Requesting from Unsplash API with keyword: {}", keyword);
+    async fn fetch_pinned_photo(&self, id: &str, keyword: &str, draw: u32) -> Result<Background> {
+        let api_key = pinned_photo_key(&self.config, id, draw)?;
+        info!("Using pinned Unsplash photo: {}", id);
+        let url = format!("{}/{}", self.context.api_url(UNSPLASH_PHOTOS_PATH), id);
+        let photo = self.unsplash_json(&url, &[], api_key).await?;
+        self.download_unsplash_photo(keyword, photo).await
+    }
 
+    async fn fetch_from_unsplash(&self, keyword: &str, api_key: &str, draw: u32) -> Result<Background> {
+        let photo = match self.config.seed {
+            Some(seed) => {
+                debug!("Searching Unsplash with keyword: {}", keyword);
+                let url = self.context.api_url(UNSPLASH_SEARCH_PATH);
+                let found: UnsplashSearchResponse = self
+                    .unsplash_json(&url, &unsplash_search_query(keyword), api_key)
+                    .await?;
+                pick_photo(found, seed, keyword, draw)?
+            }
+            None => {
+                debug!("Requesting from Unsplash API with keyword: {}", keyword);
+                let url = self.context.api_url(UNSPLASH_RANDOM_PATH);
+                self.unsplash_json(&url, &unsplash_query(keyword), api_key).await?
+            }
+        };
+        self.download_unsplash_photo(keyword, photo).await
+    }
+
+    async fn unsplash_json<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+        api_key: &str,
+    ) -> Result<T> {
         let response = self
-            .client
-            .get(UNSPLASH_API_URL)
-            .query(&[
-                ("query", keyword),
-                ("orientation", "landscape"),
-                ("content_filter", "high"),
-            ])
+            .context
+            .client()
+            .get(url)
+            .query(query)
             .header("Authorization", format!("Client-ID {}", api_key))
-            .send()?;
+            .send()
+            .await?;
 
         if !response.status().is_success() {
             return Err(QrImageError::ApiError(format!(
@@ -82,38 +430,40 @@ impl ImageProvider {
             )));
         }
 
-        let unsplash_data: UnsplashResponse = response.json()?;
-        debug!("Image description: {:?}", unsplash_data.description);
-
-        // Use 'regular' size URL with custom dimensions
-        let image_url = format!(
-            "{}&w={}&h={}&fit=crop",
-            unsplash_data.urls.raw, self.config.image_width, self.config.image_height
-        );
+        Ok(response.json().await?)
+    }
 
-        self.download_image(&image_url)
+    async fn download_unsplash_photo(&self, keyword: &str, photo: UnsplashResponse) -> Result<Background> {
+        let url = sized_image_url(&photo, &self.config);
+        let image = self.download_image(&url).await?;
+        let info = unsplash_info(&photo, keyword, &image, url);
+        Ok((image, info))
     }
 
-    fn fetch_fallback_image(&self, keyword: &str) -> Result<DynamicImage> {
+    async fn fetch_fallback_image(&self, keyword: &str, draw: u32) -> Result<Background> {
+        if self.config.seed.is_some() {
+            info!("Seeded run: skipping the random fallback photo");
+            return self.placeholder(keyword, draw).await;
+        }
         info!("Using fallback image source");
 
-        let image_url = format!(
-            "{}/?{}",
-            FALLBACK_IMAGE_URL,
-            keyword.replace(' ', "+")
-        );
-
-        self.download_image(&image_url)
-            .or_else(|_| {
+        let url = fallback_image_url(keyword);
+        match self.download_image(&url).await {
+            Ok(image) => {
+                let info = BackgroundInfo::new(BackgroundOrigin::Fallback, keyword, &image).with_url(url);
+                Ok((image, info))
+            }
+            Err(_) => {
                 warn!("Fallback failed, generating solid color image");
-                self.generate_placeholder_image(keyword)
-            })
+                self.placeholder(keyword, 0).await
+            }
+        }
     }
 
-    fn download_image(&self, url: &str) -> Result<DynamicImage> {
+    async fn download_image(&self, url: &str) -> Result<DynamicImage> {
         debug!("Downloading image from: {}", url);
 
-        let response = self.client.get(url).send()?;
+        let response = self.context.client().get(url).send().await?;
 
         if !response.status().is_success() {
             return Err(QrImageError::ApiError(format!(
@@ -122,48 +472,305 @@ impl ImageProvider {
             )));
         }
 
-        let bytes = response.bytes()?;
-        let img = image::load_from_memory(&bytes)
-            .or_else(|_| {
-                // Try to parse as specific format
-                image::load_from_memory_with_format(&bytes, ImageFormat::Jpeg)
-                    .or_else(|_| image::load_from_memory_with_format(&bytes, ImageFormat::Png))
-            })
-            .map_err(|e| QrImageError::ProviderError(format!("Failed to decode image: {}", e)))?;
-
-        // Resize to target dimensions if needed
-        let resized = img.resize_exact(
-            self.config.image_width,
-            self.config.image_height,
-            image::imageops::FilterType::Lanczos3,
-        );
+        let bytes = response.bytes().await?;
+        let config = self.config.clone();
+        spawn_blocking(move || decode_background(&bytes, &config)).await
+    }
+
+    async fn placeholder(&self, keyword: &str, draw: u32) -> Result<Background> {
+        let (keyword, config) = (keyword.to_string(), self.config.clone());
+        spawn_blocking(move || placeholder_background(&keyword, &config, draw)).await
+    }
+}
+
+impl BackgroundSource for ImageProvider {
+    fn background(&self, keyword: &str) -> Result<Background> {
+        self.fetch_background(keyword)
+    }
 
-        Ok(resized)
+    fn new_background(&self, keyword: &str, draw: u32) -> Result<Background> {
+        self.fetch_replacement_background(keyword, draw)
     }
+}
 
-    fn generate_placeholder_image(&self, keyword: &str) -> Result<DynamicImage> {
-        info!("Generating placeholder image for: {}", keyword);
+fn unsplash_query(keyword: &str) -> [(&str, &str); 3] {
+    [
+        ("query", keyword),
+        ("orientation", "landscape"),
+        ("content_filter", "high"),
+    ]
+}
 
-        // Generate a color based on keyword hash
-        let hash = keyword.bytes().fold(0u32, |acc, b| acc.wrapping_add(b as u32));
-        let r = ((hash * 137) % 256) as u8;
-        let g = ((hash * 193) % 256) as u8;
-        let b = ((hash * 241) % 256) as u8;
+fn unsplash_search_query(keyword: &str) -> [(&str, &str); 4] {
+    [
+        ("query", keyword),
+        ("orientation", "landscape"),
+        ("content_filter", "high"),
+        ("per_page", UNSPLASH_SEARCH_PAGE_SIZE),
+    ]
+}
 
-        let mut img = image::RgbImage::new(self.config.image_width, self.config.image_height);
+/// API key for fetching a pinned photo, which cannot be swapped for another
+fn pinned_photo_key<'a>(config: &'a Config, id: &str, draw: u32) -> Result<&'a str> {
+    if draw > 0 {
+        return Err(QrImageError::ProviderError(format!(
+            "background photo {} is pinned and cannot be replaced",
+            id
+        )));
+    }
+    config.unsplash_api_key.as_deref().ok_or_else(|| {
+        QrImageError::ConfigError("background_photo needs an Unsplash API key".to_string())
+    })
+}
 
-        // Create gradient effect
-        for (x, _y, pixel) in img.enumerate_pixels_mut() {
-            let factor = (x as f32 / self.config.image_width as f32) * 0.3 + 0.7;
-            *pixel = image::Rgb([
-                (r as f32 * factor) as u8,
-                (g as f32 * factor) as u8,
-                (b as f32 * factor) as u8,
-            ]);
+/// The seeded pick among a keyword's search results
+fn pick_photo(
+    found: UnsplashSearchResponse,
+    seed: u64,
+    keyword: &str,
+    draw: u32,
+) -> Result<UnsplashResponse> {
+    let len = found.results.len();
+    if len == 0 {
+        return Err(QrImageError::ApiError(format!(
+            "Unsplash found no photos for '{}'",
+            keyword
+        )));
+    }
+    let index = seeded_index(seed, keyword, draw, len);
+    Ok(found.results.into_iter().nth(index).expect("index within results"))
+}
+
+fn unsplash_info(
+    photo: &UnsplashResponse,
+    keyword: &str,
+    image: &DynamicImage,
+    url: String,
+) -> BackgroundInfo {
+    BackgroundInfo {
+        photo_id: photo.id.clone(),
+        description: unsplash_description(photo),
+        attribution: unsplash_attribution(photo),
+        photo_page: photo.links.as_ref().map(|links| links.html.clone()),
+        ..BackgroundInfo::new(BackgroundOrigin::Unsplash, keyword, image).with_url(url)
+    }
+}
+
+/// FNV-1a, stable across builds unlike `DefaultHasher`
+fn keyword_hash(keyword: &str) -> u64 {
+    keyword.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Index of the `draw`-th pick among `len` candidates: a start position
+/// fixed by the seed and keyword, then the following candidates in turn
+fn seeded_index(seed: u64, keyword: &str, draw: u32, len: usize) -> usize {
+    let start = SplitMix64::new(seed ^ keyword_hash(keyword)).next_u64() % len as u64;
+    ((start + u64::from(draw)) % len as u64) as usize
+}
+
+fn random_index(len: usize) -> usize {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or(0);
+    (SplitMix64::new(nanos).next_u64() % len as u64) as usize
+}
+
+/// Image files in `dir` by name; those whose name contains the keyword when
+/// there are any, otherwise all of them
+fn local_candidates(dir: &Path, keyword: &str) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && ImageFormat::from_path(path).is_ok())
+        .collect();
+    files.sort();
+
+    let needle = keyword.to_lowercase();
+    let matching: Vec<PathBuf> = files
+        .iter()
+        .filter(|path| {
+            path.file_stem()
+                .is_some_and(|stem| stem.to_string_lossy().to_lowercase().contains(&needle))
+        })
+        .cloned()
+        .collect();
+    Ok(if matching.is_empty() { files } else { matching })
+}
+
+/// A background from `Config::background_dir`, picked by the seed when set
+fn local_background(keyword: &str, config: &Config, draw: u32) -> Result<Background> {
+    let dir = config
+        .background_dir
+        .as_deref()
+        .ok_or_else(|| QrImageError::ConfigError("background_dir is not set".to_string()))?;
+    let candidates = local_candidates(dir, keyword)?;
+    if candidates.is_empty() {
+        return Err(QrImageError::ProviderError(format!(
+            "no images in {}",
+            dir.display()
+        )));
+    }
+
+    let index = match config.seed {
+        Some(seed) => seeded_index(seed, keyword, draw, candidates.len()),
+        None => random_index(candidates.len()),
+    };
+    let path = &candidates[index];
+    info!("Using local background: {}", path.display());
+    let image = decode_background(&std::fs::read(path)?, config)?;
+    let info = BackgroundInfo::new(BackgroundOrigin::Local, keyword, &image)
+        .with_url(path.display().to_string());
+    Ok((image, info))
+}
+
+fn placeholder_background(keyword: &str, config: &Config, draw: u32) -> Result<Background> {
+    let image = placeholder_image(keyword, config, draw)?;
+    let info = BackgroundInfo::new(BackgroundOrigin::Placeholder, keyword, &image);
+    Ok((image, info))
+}
+
+fn unsplash_attribution(unsplash_data: &UnsplashResponse) -> Option<String> {
+    let user = unsplash_data.user.as_ref()?;
+    Some(match &user.links {
+        Some(links) => format!("Photo by {} ({}) on Unsplash", user.name, links.html),
+        None => format!("Photo by {} on Unsplash", user.name),
+    })
+}
+
+fn unsplash_description(unsplash_data: &UnsplashResponse) -> Option<String> {
+    debug!("Image description: {:?}", unsplash_data.description);
+    debug!("Image alt description: {:?}", unsplash_data.alt_description);
+    unsplash_data
+        .description
+        .clone()
+        .or_else(|| unsplash_data.alt_description.clone())
+}
+
+/// Photo URL cropped server-side to the configured dimensions
+fn sized_image_url(unsplash_data: &UnsplashResponse, config: &Config) -> String {
+    // Use 'regular' size URL with custom dimensions
+    format!(
+        "{}&w={}&h={}&fit=crop",
+        unsplash_data.urls.raw, config.image_width, config.image_height
+    )
+}
+
+fn fallback_image_url(keyword: &str) -> String {
+    format!("{}/?{}", FALLBACK_IMAGE_URL, keyword.replace(' ', "+"))
+}
+
+/// Decode downloaded bytes and resize them to the configured dimensions
+fn decode_background(bytes: &[u8], config: &Config) -> Result<DynamicImage> {
+    let img = image::load_from_memory(bytes)
+        .or_else(|_| {
+            // Try to parse as specific format
+            image::load_from_memory_with_format(bytes, ImageFormat::Jpeg)
+                .or_else(|_| image::load_from_memory_with_format(bytes, ImageFormat::Png))
+        })
+        .map_err(|e| QrImageError::ProviderError(format!("Failed to decode image: {}", e)))?;
+
+    // Resize to target dimensions if needed
+    let resized = img.resize_exact(
+        config.image_width,
+        config.image_height,
+        image::imageops::FilterType::Lanczos3,
+    );
+
+    Ok(resized)
+}
+
+/// Gradient coloured from the keyword, or from the seed, keyword and draw
+/// when seeded
+fn placeholder_image(keyword: &str, config: &Config, draw: u32) -> Result<DynamicImage> {
+    info!("Generating placeholder image for: {}", keyword);
+
+    let (r, g, b) = match config.seed {
+        Some(seed) => {
+            let mut rng = SplitMix64::new(seed ^ keyword_hash(keyword));
+            (0..draw).for_each(|_| {
+                rng.next_u64();
+            });
+            let [r, g, b, ..] = rng.next_u64().to_le_bytes();
+            (r, g, b)
+        }
+        None => {
+            // Generate a color based on keyword hash
+            let hash = keyword.bytes().fold(0u32, |acc, b| acc.wrapping_add(b as u32));
+            (
+                ((hash * 137) % 256) as u8,
+                ((hash * 193) % 256) as u8,
+                ((hash * 241) % 256) as u8,
+            )
+        }
+    };
+
+    let mut img = image::RgbImage::new(config.image_width, config.image_height);
+
+    // Create gradient effect
+    for (x, _y, pixel) in img.enumerate_pixels_mut() {
+        let factor = (x as f32 / config.image_width as f32) * 0.3 + 0.7;
+        *pixel = image::Rgb([
+            (r as f32 * factor) as u8,
+            (g as f32 * factor) as u8,
+            (b as f32 * factor) as u8,
+        ]);
+    }
+
+    Ok(DynamicImage::ImageRgb8(img))
+}
+
+/// Async [`ImageProvider`] for use inside a tokio runtime: downloads run on
+/// the caller's runtime with the async HTTP client and decoding runs on the
+/// blocking thread pool. The blocking provider drives the same code on a
+/// runtime of its own. Clones share the HTTP client and background cache.
+#[cfg(feature = "async")]
+#[derive(Clone)]
+pub struct AsyncImageProvider {
+    provider: Arc<ImageProvider>,
+}
+
+#[cfg(feature = "async")]
+impl AsyncImageProvider {
+    pub fn new(config: Config) -> Self {
+        let context = ProviderContext::new(config.background_cache_size);
+        Self::with_context(config, context)
+    }
+
+    /// Create a provider sharing an HTTP client and background cache with
+    /// blocking providers and generators
+    pub fn with_context(config: Config, context: ProviderContext) -> Self {
+        Self {
+            provider: Arc::new(ImageProvider::with_context(config, context)),
         }
+    }
 
-        Ok(DynamicImage::ImageRgb8(img))
+    /// Async [`ImageProvider::fetch_background`]
+    pub async fn fetch_background(&self, keyword: &str) -> Result<Background> {
+        self.provider.load_background(keyword).await
     }
+
+    /// Async [`ImageProvider::fetch_new_background`]
+    pub async fn fetch_new_background(&self, keyword: &str) -> Result<Background> {
+        self.provider.load_replacement(keyword, 0).await
+    }
+
+    /// Async [`ImageProvider::fetch_replacement_background`]
+    pub async fn fetch_replacement_background(&self, keyword: &str, draw: u32) -> Result<Background> {
+        self.provider.load_replacement(keyword, draw).await
+    }
+}
+
+/// Run CPU-bound work on tokio's blocking thread pool
+pub(crate) async fn spawn_blocking<T, F>(work: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| QrImageError::TaskError(e.to_string()))?
 }
 
 #[cfg(test)]
@@ -173,9 +780,79 @@ mod tests {
     #[test]
     fn test_placeholder_generation() {
         let config = Config::default();
-        let provider = ImageProvider::new(config);
-        let img = provider.generate_placeholder_image("test").unwrap();
+        let img = placeholder_image("test", &config, 0).unwrap();
         assert_eq!(img.width(), 1920);
         assert_eq!(img.height(), 1080);
     }
+
+    #[test]
+    fn test_seeded_backgrounds_repeat() {
+        let config = Config::default().with_dimensions(64, 32).with_seed(7);
+        let first = placeholder_image("sea", &config, 0).unwrap();
+        assert_eq!(first, placeholder_image("sea", &config, 0).unwrap());
+        assert_ne!(first, placeholder_image("sea", &config, 1).unwrap());
+        assert_ne!(first, placeholder_image("sea", &config.clone().with_seed(8), 0).unwrap());
+
+        let dir = std::env::temp_dir().join(format!("qrgen-backgrounds-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["sea-1.png", "sea-2.png", "sea-3.png", "forest.png", "notes.txt"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        let candidates = local_candidates(&dir, "Sea").unwrap();
+        assert_eq!(candidates.len(), 3);
+        assert_eq!(local_candidates(&dir, "desert").unwrap().len(), 4);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let picks: Vec<usize> = (0..3).map(|draw| seeded_index(7, "sea", draw, 3)).collect();
+        assert_eq!(picks, (0..3).map(|draw| seeded_index(7, "sea", draw, 3)).collect::<Vec<_>>());
+        let mut distinct = picks.clone();
+        distinct.sort();
+        assert_eq!(distinct, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_pinned_photo_is_not_replaced() {
+        let config = Config::default().with_api_key("key".to_string()).with_background_photo("abc");
+        assert_eq!(pinned_photo_key(&config, "abc", 0).unwrap(), "key");
+        assert!(pinned_photo_key(&config, "abc", 1).is_err());
+    }
+
+    #[test]
+    fn test_unsplash_attribution() {
+        let response: UnsplashResponse = serde_json::from_str(
+            r#"{"id":"x","urls":{"raw":"https://images.unsplash.com/x?ixid=1"},
+                "description":null,"alt_description":"a wave",
+                "user":{"name":"Jane Doe","links":{"html":"https://unsplash.com/@jane"}},
+                "links":{"html":"https://unsplash.com/photos/x"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            unsplash_attribution(&response).as_deref(),
+            Some("Photo by Jane Doe (https://unsplash.com/@jane) on Unsplash")
+        );
+        assert_eq!(unsplash_description(&response).as_deref(), Some("a wave"));
+        let image = DynamicImage::new_rgb8(1, 1);
+        let url = sized_image_url(&response, &Config::default());
+        let info = unsplash_info(&response, "sea", &image, url);
+        assert_eq!(info.photo_id.as_deref(), Some("x"));
+        assert_eq!(info.photo_page.as_deref(), Some("https://unsplash.com/photos/x"));
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let context = ProviderContext::new(2);
+        let config = Config::default();
+        let key = |name: &str| cache_key(name, &config);
+        let image = DynamicImage::new_rgb8(1, 1);
+        let background = (image.clone(), BackgroundInfo::supplied(&image));
+
+        let mut cache = context.cache();
+        cache.insert(key("a"), background.clone());
+        cache.insert(key("b"), background.clone());
+        assert!(cache.get(&key("a")).is_some());
+        cache.insert(key("c"), background);
+        assert!(cache.get(&key("b")).is_none());
+        assert!(cache.get(&key("a")).is_some());
+        assert_eq!(cache.entries.len(), 2);
+    }
 }
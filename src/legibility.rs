@@ -0,0 +1,283 @@
+use crate::error::{QrImageError, Result};
+use image::{DynamicImage, Rgb, RgbImage};
+use imageproc::geometric_transformations::Projection;
+use serde::{Deserialize, Serialize};
+
+/// A module closer to the threshold than this fraction of the distance to
+/// its colour's mean is counted as weak
+pub const WEAK_MARGIN: f32 = 0.25;
+
+/// Sample offsets within a module, as fractions of the module size; the
+/// centre 3×3 grid avoids bleed from neighbouring modules
+const SAMPLE_OFFSETS: [f32; 3] = [-0.2, 0.0, 0.2];
+
+/// Luma distribution (0-255) of the modules meant to be one colour
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LuminanceStats {
+    pub count: usize,
+    pub mean: f32,
+    pub std_dev: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl LuminanceStats {
+    fn from_values(values: &[f32]) -> Self {
+        let count = values.len();
+        if count == 0 {
+            return Self {
+                count,
+                mean: 0.0,
+                std_dev: 0.0,
+                min: 0.0,
+                max: 0.0,
+            };
+        }
+
+        let mean = values.iter().sum::<f32>() / count as f32;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / count as f32;
+        Self {
+            count,
+            mean,
+            std_dev: variance.sqrt(),
+            min: values.iter().copied().fold(f32::INFINITY, f32::min),
+            max: values.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+        }
+    }
+}
+
+/// How clearly one module reads as its intended colour
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModuleState {
+    Strong,
+    /// On the right side of the threshold, but within [`WEAK_MARGIN`]
+    Weak,
+    /// On the wrong side of the threshold
+    Wrong,
+}
+
+/// Module-level contrast analysis of an embedded code
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LegibilityReport {
+    /// Modules per side
+    pub size: usize,
+    pub dark: LuminanceStats,
+    pub light: LuminanceStats,
+    /// WCAG contrast ratio (1-21) between the mean light and mean dark module
+    pub contrast_ratio: f32,
+    /// Global threshold midway between the dark and light means
+    pub threshold: f32,
+    /// Percentage of modules sampled on the wrong side of the threshold
+    pub wrong_percent: f32,
+    /// Percentage of modules within [`WEAK_MARGIN`] of the threshold
+    pub weak_percent: f32,
+    /// Per module, row-major: distance from the threshold towards the intended
+    /// colour, as a fraction of the distance to that colour's mean; negative
+    /// on the wrong side
+    pub margins: Vec<f32>,
+    /// Intended colour per module, row-major; `true` for dark
+    pub intended_dark: Vec<bool>,
+}
+
+impl LegibilityReport {
+    /// Classify one module (row-major index)
+    pub fn state(&self, index: usize) -> ModuleState {
+        let margin = self.margins[index];
+        if margin < 0.0 {
+            ModuleState::Wrong
+        } else if margin < WEAK_MARGIN {
+            ModuleState::Weak
+        } else {
+            ModuleState::Strong
+        }
+    }
+
+    /// Render one `cell`×`cell` square per module: green for strong modules,
+    /// amber for weak ones and red for modules on the wrong side, with dark
+    /// modules in a deeper shade so the code's structure stays visible
+    pub fn heatmap(&self, cell: u32) -> RgbImage {
+        let cell = cell.max(1);
+        let side = self.size as u32 * cell;
+        RgbImage::from_fn(side, side, |x, y| {
+            let index = (y / cell) as usize * self.size + (x / cell) as usize;
+            let dark = self.intended_dark[index];
+            match (self.state(index), dark) {
+                (ModuleState::Strong, true) => Rgb([20, 110, 60]),
+                (ModuleState::Strong, false) => Rgb([160, 225, 180]),
+                (ModuleState::Weak, true) => Rgb([190, 120, 0]),
+                (ModuleState::Weak, false) => Rgb([250, 205, 100]),
+                (ModuleState::Wrong, true) => Rgb([160, 0, 0]),
+                (ModuleState::Wrong, false) => Rgb([250, 110, 110]),
+            }
+        })
+    }
+}
+
+/// Sample every module of a code and measure how well it separates from the
+/// other colour. `corners` are the outer corners of the module area in image
+/// pixels (top-left, top-right, bottom-right, bottom-left) and
+/// `intended_dark` the colour each module was encoded with, row-major.
+pub fn analyze(
+    image: &DynamicImage,
+    corners: [(i32, i32); 4],
+    size: usize,
+    intended_dark: Vec<bool>,
+) -> Result<LegibilityReport> {
+    if size == 0 || intended_dark.len() != size * size {
+        return Err(QrImageError::ValidationError(format!(
+            "Expected {} module colours, got {}",
+            size * size,
+            intended_dark.len()
+        )));
+    }
+
+    let unit = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+    let projection =
+        Projection::from_control_points(unit, corners.map(|(x, y)| (x as f32, y as f32)))
+            .ok_or_else(|| QrImageError::ValidationError("Degenerate code corners".to_string()))?;
+
+    let rgb = image.to_rgb8();
+    let (width, height) = rgb.dimensions();
+    let samples: Vec<([f32; 3], f32)> = (0..size * size)
+        .map(|index| {
+            let (mx, my) = ((index % size) as f32, (index / size) as f32);
+            let mut sum = [0.0f32; 3];
+            for dy in SAMPLE_OFFSETS {
+                for dx in SAMPLE_OFFSETS {
+                    let unit_point = (
+                        (mx + 0.5 + dx) / size as f32,
+                        (my + 0.5 + dy) / size as f32,
+                    );
+                    let (px, py) = projection * unit_point;
+                    let px = (px.max(0.0) as u32).min(width - 1);
+                    let py = (py.max(0.0) as u32).min(height - 1);
+                    let pixel = rgb.get_pixel(px, py);
+                    for channel in 0..3 {
+                        sum[channel] += pixel[channel] as f32;
+                    }
+                }
+            }
+            let count = (SAMPLE_OFFSETS.len() * SAMPLE_OFFSETS.len()) as f32;
+            let color = sum.map(|c| c / count);
+            let luma = 0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2];
+            (color, luma)
+        })
+        .collect();
+
+    let values_of = |dark: bool| -> Vec<f32> {
+        samples
+            .iter()
+            .zip(&intended_dark)
+            .filter(|(_, &d)| d == dark)
+            .map(|((_, luma), _)| *luma)
+            .collect()
+    };
+    let dark = LuminanceStats::from_values(&values_of(true));
+    let light = LuminanceStats::from_values(&values_of(false));
+    let threshold = (dark.mean + light.mean) / 2.0;
+
+    let mean_luminance = |dark: bool| -> f32 {
+        let values: Vec<f32> = samples
+            .iter()
+            .zip(&intended_dark)
+            .filter(|(_, &d)| d == dark)
+            .map(|((color, _), _)| relative_luminance(*color))
+            .collect();
+        values.iter().sum::<f32>() / values.len().max(1) as f32
+    };
+    let (dark_luminance, light_luminance) = (mean_luminance(true), mean_luminance(false));
+    let contrast_ratio = (dark_luminance.max(light_luminance) + 0.05)
+        / (dark_luminance.min(light_luminance) + 0.05);
+
+    // Guard against a flat tile, where every module sits on the threshold
+    let half_gap = ((light.mean - dark.mean) / 2.0).abs().max(1.0);
+    let margins: Vec<f32> = samples
+        .iter()
+        .zip(&intended_dark)
+        .map(|((_, luma), &dark)| {
+            let distance = if dark { threshold - luma } else { luma - threshold };
+            distance / half_gap
+        })
+        .collect();
+
+    let percent = |count: usize| count as f32 * 100.0 / margins.len() as f32;
+    let wrong = margins.iter().filter(|&&m| m < 0.0).count();
+    let weak = margins
+        .iter()
+        .filter(|&&m| (0.0..WEAK_MARGIN).contains(&m))
+        .count();
+
+    Ok(LegibilityReport {
+        size,
+        dark,
+        light,
+        contrast_ratio,
+        threshold,
+        wrong_percent: percent(wrong),
+        weak_percent: percent(weak),
+        margins,
+        intended_dark,
+    })
+}
+
+/// WCAG relative luminance of an sRGB colour with 0-255 channels
+fn relative_luminance(color: [f32; 3]) -> f32 {
+    let linear = color.map(|c| {
+        let c = c / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    0.2126 * linear[0] + 0.7152 * linear[1] + 0.0722 * linear[2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_luminance_extremes() {
+        assert_eq!(relative_luminance([0.0; 3]), 0.0);
+        assert!((relative_luminance([255.0; 3]) - 1.0).abs() < 1e-4);
+        // Black on white is the WCAG maximum of 21:1
+        assert!(((1.0 + 0.05) / (0.0 + 0.05) - 21.0f32).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_checkerboard_analysis() {
+        // 4×4 modules of 10px, dark where x + y is even, with one light
+        // module painted mid-grey
+        let size = 4;
+        let intended: Vec<bool> = (0..size * size).map(|i| (i % size + i / size) % 2 == 0).collect();
+        let mut rgb = RgbImage::from_fn(40, 40, |x, y| {
+            if intended[(y / 10) as usize * size + (x / 10) as usize] {
+                Rgb([0, 0, 0])
+            } else {
+                Rgb([255, 255, 255])
+            }
+        });
+        for y in 10..20 {
+            for x in 0..10 {
+                rgb.put_pixel(x, y, Rgb([110, 110, 110]));
+            }
+        }
+
+        let report = analyze(
+            &DynamicImage::ImageRgb8(rgb),
+            [(0, 0), (40, 0), (40, 40), (0, 40)],
+            size,
+            intended,
+        )
+        .unwrap();
+
+        assert_eq!(report.dark.count, 8);
+        assert_eq!(report.dark.mean, 0.0);
+        assert_eq!(report.state(0), ModuleState::Strong);
+        assert_eq!(report.state(size), ModuleState::Wrong);
+        assert_eq!(report.wrong_percent, 100.0 / 16.0);
+        assert!(report.contrast_ratio > 15.0);
+        assert_eq!(report.heatmap(5).dimensions(), (20, 20));
+    }
+}
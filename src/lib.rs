@@ -1,38 +1,84 @@
+#[cfg(feature = "async")]
+pub mod async_generator;
+pub mod batch;
+pub mod binarize;
+pub mod color_simulation;
 pub mod config;
 pub mod error;
 pub mod image_provider;
+pub mod legibility;
+pub mod metadata;
+pub mod output;
+pub mod preprocess;
+pub mod preset;
+pub mod provenance;
 pub mod qr_embedder;
 pub mod qr_validator;
+pub mod repair;
+pub mod request;
+pub(crate) mod rng;
+pub mod robustness;
+pub mod segments;
+pub mod server;
+pub mod structured_append;
+pub mod symbol_decoder;
 
-use config::Config;
-use error::Result;
+use config::{CodeType, Config};
+use error::{QrImageError, Result};
 use image::DynamicImage;
-use image_provider::ImageProvider;
-use log::{error, info};
+use image_provider::{BackgroundInfo, BackgroundSource, ImageProvider, ProviderContext};
+use log::{debug, error, info, warn};
 use qr_embedder::QrEmbedder;
-use qr_validator::QrValidator;
+use metadata::ImageMetadata;
+use provenance::Provenance;
+use qr_validator::{QrValidator, ValidationOutcome, ValidationReport};
+use repair::RepairedImage;
+use request::{BackgroundSpec, GenerationRequest, GenerationResult, Placement, SuppliedBackground};
+use segments::Segment;
 
 /// Main orchestrator for QR code image generation
 pub struct QrImageGenerator {
     config: Config,
     provider: ImageProvider,
+    context: ProviderContext,
     embedder: QrEmbedder,
     validator: QrValidator,
 }
 
 impl QrImageGenerator {
-    /// Create a new QR image generator with the given configuration
-    pub fn new(config: Config) -> Self {
-        let provider = ImageProvider::new(config.clone());
+    /// Create a new QR image generator with the given configuration, failing
+    /// with every problem [`Config::validate`] finds
+    pub fn new(config: Config) -> Result<Self> {
+        let context = ProviderContext::new(config.background_cache_size);
+        Self::with_context(config, context)
+    }
+
+    /// Create a generator sharing an HTTP client and background cache with
+    /// other generators, e.g. one per thread
+    pub fn with_context(config: Config, context: ProviderContext) -> Result<Self> {
+        config.validate()?;
+
+        let provider = ImageProvider::with_context(config.clone(), context.clone());
         let embedder = QrEmbedder::new(config.clone());
-        let validator = QrValidator::new(config.max_validation_attempts);
+        let decoders = config
+            .decoders
+            .iter()
+            .map(|backend| backend.create())
+            .collect::<Result<_>>()?;
+        let validator = QrValidator::new(config.max_validation_attempts)
+            .with_preprocessing(config.preprocessing.clone())
+            .with_color_simulations(config.color_simulations.clone())
+            .with_decoders(decoders)
+            .with_decoder_agreement(config.require_decoder_agreement)
+            .with_stray_detection(config.detect_stray_codes);
 
-        Self {
+        Ok(Self {
             config,
             provider,
+            context,
             embedder,
             validator,
-        }
+        })
     }
 
     /// Create a QR code image from a keyword and data
@@ -50,42 +96,324 @@ impl QrImageGenerator {
     /// 3. Validate QR code is readable
     /// 4. Return validated image
     pub fn generate(&self, keyword: &str, qr_data: &str) -> Result<DynamicImage> {
+        self.generate_bytes(keyword, qr_data.as_bytes())
+    }
+
+    /// Create a QR code image from a keyword and arbitrary binary data
+    ///
+    /// The payload is encoded in byte mode and validated byte-for-byte, so
+    /// it does not need to be valid UTF-8.
+    pub fn generate_bytes(&self, keyword: &str, qr_data: &[u8]) -> Result<DynamicImage> {
+        self.generate_repaired(keyword, qr_data).map(|repaired| repaired.image)
+    }
+
+    /// Create a QR code image, regenerating with escalating remedies when
+    /// validation fails
+    ///
+    /// Up to `Config::repair_budget` extra attempts are made, each raising
+    /// background opacity, code size or error correction, moving the code,
+    /// or fetching a new background. Returns the first image that passes
+    /// along with the adjustments made.
+    pub fn generate_repaired(&self, keyword: &str, qr_data: &[u8]) -> Result<RepairedImage> {
+        self.generate_repaired_from(&self.provider, keyword, qr_data)
+    }
+
+    /// [`generate_repaired`](Self::generate_repaired) with backgrounds from
+    /// `source` instead of the generator's own provider
+    pub(crate) fn generate_repaired_from(
+        &self,
+        source: &dyn BackgroundSource,
+        keyword: &str,
+        qr_data: &[u8],
+    ) -> Result<RepairedImage> {
         info!("Starting QR image generation");
         info!("Keyword: {}", keyword);
         info!("QR data length: {}", qr_data.len());
 
+        if self.config.code_type == CodeType::Qr
+            && structured_append::needs_split(qr_data, self.config.error_correction.into())
+        {
+            info!("Data exceeds single QR code capacity, using Structured Append");
+            return self
+                .structured_append_from(source, keyword, qr_data)
+                .map(|(image, background_image, background)| RepairedImage {
+                    image,
+                    background_image,
+                    adjustments: Vec::new(),
+                    report: None,
+                    background,
+                });
+        }
+
+        self.repair_loop(source, keyword, qr_data, None, |embedder, background| {
+            embedder.embed_qr_bytes(background, qr_data)
+        })
+    }
+
+    /// Generate one request with its own background, style, placement and
+    /// config overrides, leaving this generator's config untouched
+    pub fn generate_request(&self, request: &GenerationRequest) -> Result<GenerationResult> {
+        let config = request.resolve_config(&self.config)?;
+        let generator = Self::with_context(config.clone(), self.context.clone())?;
+        let repaired = match &request.background {
+            BackgroundSpec::Keyword(keyword) => generator.generate_repaired(keyword, &request.data)?,
+            BackgroundSpec::Image(image) => generator.generate_repaired_from(
+                &SuppliedBackground(image),
+                "",
+                &request.data,
+            )?,
+        };
+
+        let mut final_config = config;
+        repaired
+            .adjustments
+            .iter()
+            .for_each(|adjustment| adjustment.apply(&mut final_config));
+        Ok(GenerationResult {
+            placement: Placement {
+                position: final_config.qr_position.clone(),
+                size_ratio: final_config.qr_size_ratio,
+                bounds: repaired.report.as_ref().and_then(request::code_bounds),
+            },
+            image: repaired.image,
+            background_image: repaired.background_image,
+            metadata: ImageMetadata::new(&request.data, &repaired.background),
+            background: repaired.background,
+            report: repaired.report,
+            adjustments: repaired.adjustments,
+            format: request.format,
+            config: final_config,
+        })
+    }
+
+    /// Create an image carrying the data split across up to 16 linked QR
+    /// codes (Structured Append), laid out as a grid
+    ///
+    /// Every symbol must decode and the pieces must reassemble to `qr_data`.
+    pub fn generate_structured_append(&self, keyword: &str, qr_data: &[u8]) -> Result<DynamicImage> {
+        self.structured_append_from(&self.provider, keyword, qr_data)
+            .map(|(image, _, _)| image)
+    }
+
+    fn structured_append_from(
+        &self,
+        source: &dyn BackgroundSource,
+        keyword: &str,
+        qr_data: &[u8],
+    ) -> Result<(DynamicImage, DynamicImage, BackgroundInfo)> {
+        let symbols = structured_append::split(
+            qr_data,
+            self.config.error_correction.into(),
+            self.config.max_symbol_version as i16,
+        )?;
+        info!("Split data into {} linked QR code(s)", symbols.len());
+
+        let (background, info) = source.background(keyword)?;
+        let image_with_qr = self.embedder.embed_structured(background.clone(), &symbols)?;
+
+        info!("Validating Structured Append readability...");
+        match self.validator.validate_structured(&image_with_qr, qr_data) {
+            Ok(_) => {
+                info!("✓ QR code validation successful");
+                if self.config.min_robustness_score > 0 {
+                    warn!("⚠ Robustness scoring is not supported for Structured Append");
+                }
+                Ok((image_with_qr, background, info))
+            }
+            Err(e) => {
+                error!("✗ QR code validation failed: {}", e);
+                Err(QrImageError::QrNotReadable)
+            }
+        }
+    }
+
+    /// Create a QR code image from explicitly encoded segments
+    ///
+    /// Use this instead of [`generate`](Self::generate) when the encoding
+    /// mode or character set must be controlled, e.g. Shift JIS Kanji or
+    /// EUC-KR text announced with an ECI designator.
+    pub fn generate_segments(&self, keyword: &str, segments: &[Segment]) -> Result<DynamicImage> {
+        info!("Starting QR image generation from {} segment(s)", segments.len());
+        info!("Keyword: {}", keyword);
+
+        let expected = segments::decoded_bytes(segments)?;
+        self.repair_loop(
+            &self.provider,
+            keyword,
+            &expected,
+            Some(segments),
+            |embedder, background| embedder.embed_segments(background, segments),
+        )
+        .map(|repaired| repaired.image)
+    }
+
+    /// Embed and validate, applying one more adjustment after each failed
+    /// attempt until the image passes or the repair budget is spent. Codes
+    /// built from `segments` are also checked against their intended text.
+    fn repair_loop<F>(
+        &self,
+        source: &dyn BackgroundSource,
+        keyword: &str,
+        expected: &[u8],
+        segments: Option<&[Segment]>,
+        embed: F,
+    ) -> Result<RepairedImage>
+    where
+        F: Fn(&QrEmbedder, DynamicImage) -> Result<DynamicImage>,
+    {
         // Step 1: Fetch background image
         info!("Fetching background image...");
-        let background = self.provider.fetch_image(keyword)?;
+        let (mut background, mut background_info) = source.background(keyword)?;
         info!(
             "Background image fetched: {}x{}",
             background.width(),
             background.height()
         );
 
-        // Step 2: Embed QR code
-        info!("Embedding QR code...");
-        let image_with_qr = self.embedder.embed_qr_code(background, qr_data)?;
-        info!("QR code embedded successfully");
+        let budget = self.config.repair_budget as usize;
+        let mut config = self.config.clone();
+        let mut adjustments = Vec::new();
+        let mut last_error = QrImageError::QrNotReadable;
+        let mut draws = 0;
 
-        // Step 3: Validate QR code
+        for attempt in 0..=budget {
+            if attempt > 0 {
+                let Some(adjustment) = repair::next_adjustment(&config, attempt - 1) else {
+                    break;
+                };
+                warn!("Repair attempt {}/{}: {}", attempt, budget, adjustment);
+                if adjustment == repair::Adjustment::NewBackground {
+                    draws += 1;
+                    match source.new_background(keyword, draws) {
+                        Ok((image, info)) => (background, background_info) = (image, info),
+                        // A caller-supplied background cannot be replaced
+                        Err(e) => {
+                            warn!("Adjustment could not be applied: {}", e);
+                            continue;
+                        }
+                    }
+                }
+                adjustment.apply(&mut config);
+                adjustments.push(adjustment);
+            }
+
+            // Step 2: Embed QR code
+            info!("Embedding QR code...");
+            let image_with_qr = match embed(&QrEmbedder::new(config.clone()), background.clone()) {
+                Ok(image) => image,
+                // A remedy such as higher error correction may not fit the data
+                Err(e) if attempt > 0 => {
+                    warn!("Adjustment could not be applied: {}", e);
+                    if let Some(adjustment) = adjustments.pop() {
+                        config = self.config.clone();
+                        adjustments.iter().for_each(|a| a.apply(&mut config));
+                        debug!("Reverted: {}", adjustment);
+                    }
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            // Step 3: Validate QR code
+            match self.validated(image_with_qr, expected, segments) {
+                Ok((image, report)) => {
+                    if !adjustments.is_empty() {
+                        info!("✓ Repaired after {} adjustment(s)", adjustments.len());
+                    }
+                    return Ok(RepairedImage {
+                        image,
+                        background_image: background,
+                        adjustments,
+                        report,
+                        background: background_info,
+                    });
+                }
+                Err(
+                    e @ (QrImageError::QrNotReadable
+                    | QrImageError::UnexpectedQrCode { .. }
+                    | QrImageError::RobustnessTooLow { .. }),
+                ) => {
+                    last_error = e;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error)
+    }
+
+    /// Validate the embedded code, returning the image only if it is readable,
+    /// along with the validation report unless validation was skipped
+    fn validated(
+        &self,
+        image_with_qr: DynamicImage,
+        expected: &[u8],
+        segments: Option<&[Segment]>,
+    ) -> Result<(DynamicImage, Option<ValidationReport>)> {
         info!("Validating QR code readability...");
-        match self.validator.validate(&image_with_qr, qr_data) {
-            Ok(true) => {
-                info!("✓ QR code validation successful");
-                Ok(image_with_qr)
+        let outcome = match segments {
+            Some(segments) => {
+                self.validator
+                    .validate_segment_code(&image_with_qr, segments, self.config.code_type)
+            }
+            None => self
+                .validator
+                .validate_code(&image_with_qr, expected, self.config.code_type),
+        };
+        match outcome {
+            Ok(ValidationOutcome::Verified(report)) => {
+                info!(
+                    "✓ QR code validation successful (version {}, EC {:?}, {})",
+                    report.version, report.ec_level, report.strategy
+                );
+                self.check_robustness(&image_with_qr, expected)?;
+                Ok((image_with_qr, Some(report)))
             }
-            Ok(false) => {
-                error!("✗ QR code validation failed - readable but data mismatch");
-                Err(error::QrImageError::QrNotReadable)
+            Ok(ValidationOutcome::Skipped { reason }) => {
+                warn!("⚠ QR code validation skipped: {}", reason);
+                if self.config.min_robustness_score > 0 {
+                    warn!("⚠ Robustness score not checked: {}", reason);
+                }
+                Ok((image_with_qr, None))
+            }
+            Err(e @ QrImageError::UnexpectedQrCode { .. }) => {
+                error!("✗ QR code validation failed: {}", e);
+                Err(e)
             }
             Err(e) => {
                 error!("✗ QR code validation failed: {}", e);
-                Err(error::QrImageError::QrNotReadable)
+                Err(QrImageError::QrNotReadable)
             }
         }
     }
 
+    /// Enforce the configured minimum robustness score, if any
+    fn check_robustness(&self, image: &DynamicImage, expected: &[u8]) -> Result<()> {
+        let required = self.config.min_robustness_score;
+        if required == 0 {
+            return Ok(());
+        }
+
+        let report = self.validator.robustness_report(image, expected);
+        for failure in report.failures() {
+            warn!("Not readable after {}", failure.degradation);
+        }
+
+        if report.score < required {
+            error!(
+                "✗ Robustness score {} is below the minimum {}",
+                report.score, required
+            );
+            return Err(QrImageError::RobustnessTooLow {
+                score: report.score,
+                required,
+            });
+        }
+
+        info!("✓ Robustness score {}/100 (minimum {})", report.score, required);
+        Ok(())
+    }
+
     /// Generate and save QR code image to file
     ///
     /// # Arguments
@@ -101,15 +429,36 @@ impl QrImageGenerator {
         qr_data: &str,
         output_path: &str,
     ) -> Result<()> {
-        let image = self.generate(keyword, qr_data)?;
+        self.generate_bytes_and_save(keyword, qr_data.as_bytes(), output_path)
+    }
+
+    /// Generate a QR code image from binary data and save it to file
+    pub fn generate_bytes_and_save(
+        &self,
+        keyword: &str,
+        qr_data: &[u8],
+        output_path: &str,
+    ) -> Result<()> {
+        let result = self.generate_request(&GenerationRequest::new(keyword, qr_data))?;
+        let path = std::path::Path::new(output_path);
 
         info!("Saving image to: {}", output_path);
-        image.save(output_path)?;
+        output::save_with_metadata(&result.image, path, self.config.dpi, result.embedded_metadata())?;
         info!("✓ Image saved successfully");
 
+        if self.config.provenance {
+            let sidecar = Provenance::from_result(&result, qr_data).write_sidecar(path)?;
+            info!("✓ Provenance saved to: {}", sidecar.display());
+        }
+
         Ok(())
     }
 
+    /// Configuration this generator was created with
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
     /// Quick validation check without full generation
     pub fn quick_validate(&self, image: &DynamicImage) -> bool {
         self.validator.quick_check(image)
@@ -119,11 +468,48 @@ impl QrImageGenerator {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use config::QrPosition;
+    use image_provider::BackgroundOrigin;
+    use output::OutputFormat;
 
     #[test]
     fn test_generator_creation() {
         let config = Config::default();
-        let generator = QrImageGenerator::new(config);
-        assert!(true); // Basic instantiation test
+        let generator = QrImageGenerator::new(config).unwrap();
+        assert_eq!(generator.config().image_width, 1920);
+    }
+
+    #[test]
+    fn test_request_on_supplied_background() {
+        let generator = QrImageGenerator::new(Config::default()).unwrap();
+        let request = GenerationRequest::new("", "https://example.com")
+            .with_background(DynamicImage::new_rgb8(800, 600))
+            .with_position(QrPosition::TopLeft)
+            .with_format(OutputFormat::Jpeg);
+
+        let result = generator.generate_request(&request).unwrap();
+        assert_eq!(result.image.width(), 800);
+        assert_eq!(result.background.origin, BackgroundOrigin::Supplied);
+        assert_eq!(result.placement.position, QrPosition::TopLeft);
+        let (x, y, _, _) = result.placement.bounds.unwrap();
+        assert!(x < 400 && y < 300);
+        assert_eq!(&result.encode().unwrap()[..2], &[0xff, 0xd8]);
+        // The generator's own config is unchanged
+        assert_eq!(generator.config().image_width, 1920);
+    }
+
+    #[test]
+    fn test_generator_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<QrImageGenerator>();
+    }
+
+    #[test]
+    fn test_generator_rejects_invalid_config() {
+        let config = Config::default().with_dimensions(0, 1080);
+        assert!(matches!(
+            QrImageGenerator::new(config),
+            Err(QrImageError::ConfigError(_))
+        ));
     }
 }
@@ -1,20 +1,51 @@
-use clap::Parser;
-use qr_code_generator::config::{Config, QrPosition};
+use base64::Engine;
+use clap::{ArgGroup, Parser, Subcommand};
+use qr_code_generator::batch::{self, BatchStatus};
+use qr_code_generator::config::{CodeType, Config, ErrorCorrection, QrPosition};
+use qr_code_generator::metadata::{self, ImageMetadata};
+use qr_code_generator::output;
+use qr_code_generator::preset::Preset;
+use qr_code_generator::provenance::Provenance;
+use qr_code_generator::request::GenerationRequest;
+use qr_code_generator::server::{Server, ServerOptions};
+use qr_code_generator::qr_validator::{DecodedCode, DecoderBackend, QrValidator};
 use qr_code_generator::QrImageGenerator;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::process;
 
+/// Heatmap cell size in pixels per module
+const HEATMAP_CELL: u32 = 8;
+
 #[derive(Parser, Debug)]
 #[command(name = "QR Image Generator")]
 #[command(version = "1.0.0")]
 #[command(about = "Generate beautiful QR code images with keyword-based backgrounds", long_about = None)]
+#[command(group(ArgGroup::new("payload").required(true).args(["data", "data_file", "data_hex", "data_base64"])))]
+#[command(subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Keyword for background image search
-    #[arg(short, long)]
-    keyword: String,
+    #[arg(short, long, required = true)]
+    keyword: Option<String>,
 
     /// Data to encode in QR code (URL, text, etc.)
     #[arg(short, long)]
-    data: String,
+    data: Option<String>,
+
+    /// Read binary data to encode from a file ("-" for stdin)
+    #[arg(long)]
+    data_file: Option<PathBuf>,
+
+    /// Binary data to encode, given as hexadecimal
+    #[arg(long)]
+    data_hex: Option<String>,
+
+    /// Binary data to encode, given as standard base64
+    #[arg(long)]
+    data_base64: Option<String>,
 
     /// Output file path
     #[arg(short, long, default_value = "qr_output.png")]
@@ -24,29 +55,182 @@ struct Args {
     #[arg(long)]
     api_key: Option<String>,
 
-    /// Image width in pixels
-    #[arg(long, default_value = "1920")]
-    width: u32,
+    /// Config file (default: ./qrgen.toml, then $XDG_CONFIG_HOME/qrgen/config.toml)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
 
-    /// Image height in pixels
-    #[arg(long, default_value = "1080")]
-    height: u32,
+    /// Output preset (see `presets`); individual flags still override it
+    #[arg(long)]
+    preset: Option<String>,
 
-    /// QR code size as percentage (0.1 to 0.5)
-    #[arg(long, default_value = "0.25")]
-    qr_size: f32,
+    /// Image width in pixels [default: 1920]
+    #[arg(long)]
+    width: Option<u32>,
 
-    /// QR code position: top-left, top-right, bottom-left, bottom-right, center
-    #[arg(long, default_value = "bottom-right")]
-    position: String,
+    /// Image height in pixels [default: 1080]
+    #[arg(long)]
+    height: Option<u32>,
+
+    /// QR code size as percentage (0.1 to 0.5) [default: 0.25]
+    #[arg(long)]
+    qr_size: Option<f32>,
+
+    /// QR code position: top-left, top-right, bottom-left, bottom-right, center [default: bottom-right]
+    #[arg(long)]
+    position: Option<String>,
+
+    /// Print resolution recorded in PNG and JPEG output
+    #[arg(long)]
+    dpi: Option<u32>,
+
+    /// Seed for reproducible background picks and placeholder colours
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Unsplash photo id to use as the background instead of searching
+    #[arg(long)]
+    background_photo: Option<String>,
+
+    /// Directory of images to pick backgrounds from before trying Unsplash
+    #[arg(long)]
+    background_dir: Option<PathBuf>,
+
+    /// Symbol type: qr, micro [default: qr]
+    #[arg(long)]
+    code_type: Option<String>,
+
+    /// Error correction level: low, medium, quartile, high [default: medium]
+    #[arg(long)]
+    ec_level: Option<String>,
+
+    /// Decoders that cross-check the result: rqrr, rxing (comma-separated) [default: rqrr]
+    #[arg(long, value_delimiter = ',')]
+    decoders: Vec<String>,
+
+    /// Fail validation unless every decoder reads the code
+    #[arg(long)]
+    require_agreement: bool,
+
+    /// Try every preprocessing strategy after the code is read, to reject
+    /// stray codes only a later one finds
+    #[arg(long)]
+    detect_stray_codes: bool,
+
+    /// Minimum robustness score (0-100) under simulated degradations; 0 disables [default: 0]
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+    min_score: Option<u8>,
+
+    /// Write the validation report (version, EC level, mask, corners, ...) as JSON
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Analyse module contrast of the result and save a per-module heatmap
+    /// (green strong, amber weak, red wrong side of threshold)
+    #[arg(long)]
+    heatmap: Option<PathBuf>,
+
+    /// Write <output>.provenance.json (e.g. poster.png.provenance.json) recording
+    /// payload hash, symbol, placement, background attribution, config and
+    /// validation results
+    #[arg(long)]
+    provenance: bool,
+
+    /// Embed the payload, generator and photo credit in the saved image
+    #[arg(long)]
+    embed_metadata: bool,
+
+    /// Extra regeneration attempts with escalating fixes when validation fails [default: 5]
+    #[arg(long)]
+    repair_budget: Option<u32>,
 
     /// Enable verbose logging
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     verbose: bool,
 
-    /// QR code background opacity (0-255)
-    #[arg(long, default_value = "230")]
-    opacity: u8,
+    /// QR code background opacity (0-255) [default: 230]
+    #[arg(long)]
+    opacity: Option<u8>,
+
+    /// Colour of dark modules, as hex RGB (e.g. 1a237e) [default: 000000]
+    #[arg(long, value_parser = parse_color)]
+    dark_color: Option<[u8; 3]>,
+
+    /// Colour of light modules and the tile behind the code, as hex RGB [default: ffffff]
+    #[arg(long, value_parser = parse_color)]
+    light_color: Option<[u8; 3]>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Find and decode every QR code in existing images
+    Decode {
+        /// Image files to scan
+        #[arg(required = true)]
+        images: Vec<PathBuf>,
+
+        /// Output format: text, json
+        #[arg(long, default_value = "text")]
+        format: String,
+
+        /// Show the payload recorded in an image's metadata when no code can
+        /// be read from it; the exit code is still non-zero
+        #[arg(long)]
+        metadata_fallback: bool,
+    },
+    /// Generate every row of a CSV or JSONL manifest (columns keyword, data,
+    /// output, plus optional preset and config field overrides)
+    Batch {
+        /// Manifest file (.csv or .jsonl)
+        manifest: PathBuf,
+
+        /// Write per-row status, timing and validation results as JSON
+        #[arg(long)]
+        summary: Option<PathBuf>,
+
+        /// Rows generated concurrently [default: 4]
+        #[arg(short, long)]
+        jobs: Option<usize>,
+    },
+    /// Run a local HTTP service: POST /generate, POST /decode, GET /health
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        bind: String,
+
+        /// Requests handled at once [default: 4]
+        #[arg(long)]
+        workers: Option<usize>,
+
+        /// Largest request body accepted, in bytes [default: 10485760]
+        #[arg(long)]
+        max_body_bytes: Option<usize>,
+
+        /// Largest image (width × height) a request may ask for [default: 25000000]
+        #[arg(long)]
+        max_pixels: Option<u64>,
+
+        /// Largest repair_budget a request may ask for [default: 5]
+        #[arg(long)]
+        max_repair_budget: Option<u32>,
+    },
+    /// List built-in and user-defined output presets
+    Presets,
+    /// Inspect configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Print the effective configuration: defaults, then the config file, then
+    /// environment variables, then the flags given before `config`
+    Show {
+        /// Output format: toml, json, yaml
+        #[arg(long, default_value = "toml")]
+        format: String,
+    },
 }
 
 fn main() {
@@ -59,55 +243,160 @@ fn main() {
         .format_module_path(false)
         .init();
 
-    // Parse QR position
-    let qr_position = match args.position.to_lowercase().as_str() {
-        "top-left" => QrPosition::TopLeft,
-        "top-right" => QrPosition::TopRight,
-        "bottom-left" => QrPosition::BottomLeft,
-        "bottom-right" => QrPosition::BottomRight,
-        "center" => QrPosition::Center,
-        _ => {
-            eprintln!("Invalid position. Use: top-left, top-right, bottom-left, bottom-right, or center");
-            process::exit(1);
+    match &args.command {
+        Some(Command::Decode {
+            images,
+            format,
+            metadata_fallback,
+        }) => process::exit(run_decode(&args, images, format, *metadata_fallback)),
+        Some(Command::Config {
+            action: ConfigAction::Show { format },
+        }) => process::exit(show_config(&args, format)),
+        Some(Command::Batch {
+            manifest,
+            summary,
+            jobs,
+        }) => process::exit(run_batch(&args, manifest, summary.as_deref(), *jobs)),
+        Some(Command::Serve {
+            bind,
+            workers,
+            max_body_bytes,
+            max_pixels,
+            max_repair_budget,
+        }) => {
+            let mut options = ServerOptions::default();
+            if let Some(workers) = workers {
+                options = options.with_workers(*workers);
+            }
+            if let Some(bytes) = max_body_bytes {
+                options = options.with_max_body_bytes(*bytes);
+            }
+            if let Some(pixels) = max_pixels {
+                options = options.with_max_image_pixels(*pixels);
+            }
+            if let Some(budget) = max_repair_budget {
+                options = options.with_max_repair_budget(*budget);
+            }
+            process::exit(serve(&args, bind, options))
         }
-    };
-
-    // Validate QR size
-    if !(0.1..=0.5).contains(&args.qr_size) {
-        eprintln!("QR size must be between 0.1 and 0.5");
-        process::exit(1);
+        Some(Command::Presets) => process::exit(list_presets(&args)),
+        None => {}
     }
+    let keyword = args.keyword.clone().unwrap_or_default();
 
-    // Build configuration
-    let mut config = Config::default()
-        .with_dimensions(args.width, args.height)
-        .with_qr_size_ratio(args.qr_size)
-        .with_qr_position(qr_position);
-
-    config.qr_background_opacity = args.opacity;
+    // Layer defaults, config file, environment and flags
+    let config = match build_config(&args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
 
-    // Use API key from args, or fall back to environment variable
-    let api_key = args.api_key.or_else(|| std::env::var("UNSPLASH_API_KEY").ok());
-    if let Some(key) = api_key {
-        config = config.with_api_key(key);
-    }
+    // Resolve QR payload
+    let payload = match read_payload(&args) {
+        Ok(payload) => payload,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
 
     // Create generator
-    let generator = QrImageGenerator::new(config);
+    let generator = match QrImageGenerator::new(config) {
+        Ok(generator) => generator,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    let config = generator.config();
 
     // Generate image
     println!("🎨 Generating QR code image...");
-    println!("📝 Keyword: {}", args.keyword);
-    println!("🔗 QR Data: {}", args.data);
+    println!("📝 Keyword: {}", keyword);
+    match std::str::from_utf8(&payload) {
+        Ok(text) => println!("🔗 QR Data: {}", text),
+        Err(_) => println!("🔗 QR Data: <{} bytes of binary data>", payload.len()),
+    }
     println!();
 
-    match generator.generate_and_save(&args.keyword, &args.data, &args.output) {
-        Ok(()) => {
+    let request = GenerationRequest::new(&keyword, payload.clone());
+    let result = generator
+        .generate_request(&request)
+        .and_then(|generated| {
+            let output_path = Path::new(&args.output);
+            output::save_with_metadata(
+                &generated.image,
+                output_path,
+                config.dpi,
+                generated.embedded_metadata(),
+            )?;
+            if config.provenance {
+                let sidecar = Provenance::from_result(&generated, &payload).write_sidecar(output_path)?;
+                println!("🧾 Provenance saved to: {}", sidecar.display());
+            }
+            if let Some(path) = &args.report {
+                match &generated.report {
+                    Some(report) => std::fs::write(path, report.to_json()?)?,
+                    None => eprintln!("⚠ No validation report available; {} not written", path.display()),
+                }
+            }
+            if let Some(report) = &generated.report {
+                for check in report.color_checks.iter().filter(|check| !check.passed) {
+                    println!("⚠ Not readable under {} simulation", check.simulation);
+                }
+                for check in report.decoder_checks.iter().filter(|check| !check.passed) {
+                    println!("⚠ Not readable by the {} decoder", check.decoder);
+                }
+            }
+            if let Some(path) = &args.heatmap {
+                let validator = QrValidator::new(config.max_validation_attempts)
+                    .with_preprocessing(config.preprocessing.clone());
+                match validator.legibility(&generated.image) {
+                    Ok(legibility) => {
+                        println!(
+                            "🔬 Contrast {:.1}:1 (dark {:.0}±{:.0}, light {:.0}±{:.0}), {:.1}% modules wrong side, {:.1}% weak",
+                            legibility.contrast_ratio,
+                            legibility.dark.mean,
+                            legibility.dark.std_dev,
+                            legibility.light.mean,
+                            legibility.light.std_dev,
+                            legibility.wrong_percent,
+                            legibility.weak_percent
+                        );
+                        legibility.heatmap(HEATMAP_CELL).save(path)?;
+                        println!("🗺  Heatmap saved to: {}", path.display());
+                    }
+                    Err(e) => eprintln!("⚠ Legibility analysis failed: {}", e),
+                }
+            }
+            Ok((generated.adjustments, generated.background))
+        });
+
+    match result {
+        Ok((adjustments, background)) => {
             println!();
             println!("✅ Success! QR code image generated.");
             println!("📁 Saved to: {}", args.output);
+            if let Some(id) = &background.photo_id {
+                println!("📷 Unsplash photo {} (reuse it with --background-photo {})", id, id);
+            }
+            if let Some(attribution) = &background.attribution {
+                println!("   {}", attribution);
+            }
+            if !adjustments.is_empty() {
+                println!("🔧 Adjustments made to pass validation:");
+                for adjustment in &adjustments {
+                    println!("  • {}", adjustment);
+                }
+            }
             println!();
-            println!("The QR code has been validated and is guaranteed to be readable!");
+            if config.code_type == CodeType::Qr {
+                println!("The QR code has been validated and is guaranteed to be readable!");
+            } else {
+                println!("⚠ Micro QR codes cannot be validated automatically; scan-test before printing.");
+            }
         }
         Err(e) => {
             eprintln!();
@@ -123,6 +412,444 @@ fn main() {
     }
 }
 
+/// Effective configuration: defaults, then the config file, then environment
+/// variables, then command-line flags
+fn build_config(args: &Args) -> Result<Config, String> {
+    let config = Config::load(args.config.as_deref()).map_err(|e| e.to_string())?;
+    build_config_from(config, args)
+}
+
+/// Apply the preset and flags to an already loaded configuration
+fn build_config_from(mut config: Config, args: &Args) -> Result<Config, String> {
+    if let Some(name) = &args.preset {
+        let preset = Preset::find(&config, name).ok_or_else(|| {
+            format!("Unknown preset '{}'. Run `presets` to list them", name)
+        })?;
+        config = config.with_preset(&preset);
+    }
+    if let Some(width) = args.width {
+        config.image_width = width;
+    }
+    if let Some(height) = args.height {
+        config.image_height = height;
+    }
+    if let Some(qr_size) = args.qr_size {
+        config.qr_size_ratio = qr_size;
+    }
+    if let Some(position) = &args.position {
+        config.qr_position = match position.to_lowercase().as_str() {
+            "top-left" => QrPosition::TopLeft,
+            "top-right" => QrPosition::TopRight,
+            "bottom-left" => QrPosition::BottomLeft,
+            "bottom-right" => QrPosition::BottomRight,
+            "center" => QrPosition::Center,
+            _ => {
+                return Err(
+                    "Invalid position. Use: top-left, top-right, bottom-left, bottom-right, or center"
+                        .to_string(),
+                )
+            }
+        };
+    }
+    if let Some(dpi) = args.dpi {
+        config.dpi = Some(dpi);
+    }
+    if let Some(code_type) = &args.code_type {
+        config.code_type = match code_type.to_lowercase().as_str() {
+            "qr" => CodeType::Qr,
+            "micro" | "micro-qr" => CodeType::MicroQr,
+            "rmqr" => {
+                return Err(
+                    "Rectangular Micro QR (rMQR) is not supported by the QR encoder".to_string(),
+                )
+            }
+            _ => return Err("Invalid code type. Use: qr or micro".to_string()),
+        };
+    }
+    if let Some(ec_level) = &args.ec_level {
+        config.error_correction = match ec_level.to_lowercase().as_str() {
+            "low" | "l" => ErrorCorrection::Low,
+            "medium" | "m" => ErrorCorrection::Medium,
+            "quartile" | "q" => ErrorCorrection::Quartile,
+            "high" | "h" => ErrorCorrection::High,
+            _ => {
+                return Err(
+                    "Invalid error correction level. Use: low, medium, quartile, or high"
+                        .to_string(),
+                )
+            }
+        };
+    }
+    if !args.decoders.is_empty() {
+        config.decoders = args
+            .decoders
+            .iter()
+            .map(|name| match name.to_lowercase().as_str() {
+                "rqrr" => Ok(DecoderBackend::Rqrr),
+                "rxing" | "zxing" => Ok(DecoderBackend::Rxing),
+                _ => Err(format!("Invalid decoder '{}'. Use: rqrr or rxing", name)),
+            })
+            .collect::<Result<_, _>>()?;
+    }
+    if args.provenance {
+        config.provenance = true;
+    }
+    if args.embed_metadata {
+        config.embed_metadata = true;
+    }
+    if let Some(seed) = args.seed {
+        config.seed = Some(seed);
+    }
+    if let Some(id) = &args.background_photo {
+        config.background_photo = Some(id.clone());
+    }
+    if let Some(dir) = &args.background_dir {
+        config.background_dir = Some(dir.clone());
+    }
+    if args.require_agreement {
+        config.require_decoder_agreement = true;
+    }
+    if args.detect_stray_codes {
+        config.detect_stray_codes = true;
+    }
+    if let Some(min_score) = args.min_score {
+        config.min_robustness_score = min_score;
+    }
+    if let Some(repair_budget) = args.repair_budget {
+        config.repair_budget = repair_budget;
+    }
+    if let Some(opacity) = args.opacity {
+        config.qr_background_opacity = opacity;
+    }
+    if let Some(dark) = args.dark_color {
+        config.dark_color = dark;
+    }
+    if let Some(light) = args.light_color {
+        config.light_color = light;
+    }
+    if let Some(key) = &args.api_key {
+        config.unsplash_api_key = Some(key.clone());
+    }
+
+    config.validate().map_err(|e| e.to_string())?;
+    Ok(config)
+}
+
+/// Print the configuration the generator would start from; returns the
+/// process exit code
+fn show_config(args: &Args, format: &str) -> i32 {
+    let mut config = match build_config(args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    if config.unsplash_api_key.is_some() {
+        config.unsplash_api_key = Some("<redacted>".to_string());
+    }
+
+    let output = match format.to_lowercase().as_str() {
+        "toml" => config.to_toml().map_err(|e| e.to_string()),
+        "json" => serde_json::to_string_pretty(&config).map_err(|e| e.to_string()),
+        "yaml" | "yml" => serde_yaml::to_string(&config).map_err(|e| e.to_string()),
+        _ => Err("Invalid output format. Use: toml, json or yaml".to_string()),
+    };
+    match output {
+        Ok(output) => {
+            println!("{}", output.trim_end());
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+/// Generate a manifest and print one line per row; returns the process exit
+/// code, which is non-zero if the manifest was unusable or any row failed
+fn run_batch(args: &Args, manifest: &Path, summary_path: Option<&Path>, jobs: Option<usize>) -> i32 {
+    let loaded = Config::load(args.config.as_deref())
+        .map(|config| match jobs {
+            Some(jobs) => config.with_batch_workers(jobs),
+            None => config,
+        })
+        .and_then(|config| config.validate().map(|_| config))
+        .and_then(|config| Ok((batch::read_manifest(manifest)?, config)));
+    let (items, config) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+
+    println!(
+        "📦 Generating {} item(s) from {} with {} worker(s)",
+        items.len(),
+        manifest.display(),
+        config.batch_workers
+    );
+    let summary = batch::run(&config, &items, |row| match row.status {
+        BatchStatus::Succeeded => println!(
+            "✅ [{}/{}] {} ({:.0} ms{})",
+            row.row,
+            items.len(),
+            row.output.display(),
+            row.duration_ms,
+            if row.adjustments.is_empty() {
+                String::new()
+            } else {
+                format!(", {} repair(s)", row.adjustments.len())
+            }
+        ),
+        BatchStatus::Failed => println!(
+            "❌ [{}/{}] {}: {}",
+            row.row,
+            items.len(),
+            row.output.display(),
+            row.error.as_deref().unwrap_or_default()
+        ),
+    });
+    println!(
+        "\n{} succeeded, {} failed in {:.1} s",
+        summary.succeeded,
+        summary.failed,
+        summary.duration_ms / 1000.0
+    );
+
+    if let Some(path) = summary_path {
+        if let Err(e) = summary
+            .to_json()
+            .and_then(|json| Ok(std::fs::write(path, json)?))
+        {
+            eprintln!("Failed to write summary {}: {}", path.display(), e);
+            return 1;
+        }
+    }
+    if summary.failed > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+/// Run the HTTP service until the process is killed; returns the process
+/// exit code if it cannot start
+fn serve(args: &Args, bind: &str, options: ServerOptions) -> i32 {
+    let server = Config::load(args.config.as_deref())
+        .and_then(|config| Server::bind(bind, config, options));
+    match server {
+        Ok(server) => {
+            if let Some(address) = server.local_addr() {
+                println!("🌐 Listening on http://{}", address);
+            }
+            server.run();
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+/// Print every preset available with the loaded configuration; returns the
+/// process exit code
+fn list_presets(args: &Args) -> i32 {
+    let config = match Config::load(args.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+
+    for (name, preset) in Preset::all(&config) {
+        let source = if config.presets.contains_key(&name) { " (user)" } else { "" };
+        println!(
+            "{:<18} {:>5}×{:<5} qr {:.2}  {:<12} {:>3} dpi  {}{}",
+            name,
+            preset.width,
+            preset.height,
+            preset.qr_size_ratio,
+            format!("{:?}", preset.position),
+            preset.dpi,
+            preset.description,
+            source
+        );
+    }
+    0
+}
+
+/// Decode every image and print what was found; returns the process exit code,
+/// which is non-zero if any image could not be read or held no QR code
+fn run_decode(args: &Args, images: &[PathBuf], format: &str, metadata_fallback: bool) -> i32 {
+    let json = match format.to_lowercase().as_str() {
+        "text" => false,
+        "json" => true,
+        _ => {
+            eprintln!("Invalid output format. Use: text or json");
+            return 1;
+        }
+    };
+
+    let loaded = Config::load(args.config.as_deref())
+        .and_then(|config| config.validate().map(|_| config));
+    let config = match loaded {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    let validator =
+        QrValidator::new(config.max_validation_attempts).with_preprocessing(config.preprocessing);
+    let mut exit_code = 0;
+    let mut results = Vec::new();
+
+    for path in images {
+        let loaded = std::fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| match image::load_from_memory(&bytes) {
+                Ok(image) => Ok((image, metadata::read(&bytes))),
+                Err(e) => Err(e.to_string()),
+            });
+        let (codes, embedded) = match loaded {
+            Ok((image, embedded)) => (validator.decode_all(&image), embedded),
+            Err(e) => {
+                eprintln!("❌ {}: {}", path.display(), e);
+                exit_code = 1;
+                continue;
+            }
+        };
+        let matches = embedded.as_ref().map(|embedded| embedded.matches(&codes));
+        let fallback = codes.is_empty() && metadata_fallback && embedded.is_some();
+        // Without a scanned code, metadata is only shown when asked for
+        let embedded = embedded.filter(|_| !codes.is_empty() || fallback);
+        if codes.is_empty() {
+            eprintln!("❌ {}: no QR code found", path.display());
+            exit_code = 1;
+        } else if matches == Some(false) {
+            eprintln!("⚠ {}: embedded payload differs from the scanned code(s)", path.display());
+            exit_code = 1;
+        }
+
+        if json {
+            let source = if !codes.is_empty() {
+                Some("scan")
+            } else if fallback {
+                Some("metadata")
+            } else {
+                None
+            };
+            results.push(serde_json::json!({
+                "file": path.display().to_string(),
+                "source": source,
+                "codes": codes.iter().map(DecodedCode::to_json).collect::<Vec<_>>(),
+                "metadata": embedded.as_ref().map(ImageMetadata::to_json),
+                "metadata_matches": matches.filter(|_| !codes.is_empty()),
+            }));
+            continue;
+        }
+        if !codes.is_empty() {
+            println!("📁 {}: {} QR code(s)", path.display(), codes.len());
+            for code in &codes {
+                print_decoded(code);
+            }
+        }
+        if let Some(embedded) = &embedded {
+            if fallback {
+                println!("📁 {}: read from embedded metadata instead", path.display());
+                match std::str::from_utf8(&embedded.payload) {
+                    Ok(text) => println!("  🔗 {}", text),
+                    Err(_) => println!("  🔗 <{} bytes of binary data> {}", embedded.payload.len(), hex::encode(&embedded.payload)),
+                }
+            } else if matches == Some(true) {
+                println!("  🏷  Embedded metadata matches");
+            }
+            println!("     generated by {}", embedded.generator);
+            if let Some(attribution) = &embedded.attribution {
+                println!("     {}", attribution);
+            }
+        }
+    }
+
+    if json {
+        match serde_json::to_string_pretty(&results) {
+            Ok(output) => println!("{}", output),
+            Err(e) => {
+                eprintln!("❌ Error: {}", e);
+                return 1;
+            }
+        }
+    }
+    exit_code
+}
+
+fn print_decoded(code: &DecodedCode) {
+    match code.text() {
+        Some(text) => println!("  🔗 {}", text),
+        None => println!("  🔗 <{} bytes of binary data> {}", code.data.len(), hex::encode(&code.data)),
+    }
+    println!(
+        "     version {}, EC {:?}, mask {}, found with {}",
+        code.version, code.ec_level, code.mask, code.strategy
+    );
+    println!("     corners {:?}", code.corners);
+    if let Some(header) = code.structured_append {
+        println!(
+            "     Structured Append symbol {} of {} (parity {:#04x})",
+            header.index + 1,
+            header.total,
+            header.parity
+        );
+    }
+}
+
+/// Parse a hex RGB colour such as `1a237e` or `#1A237E`
+fn parse_color(value: &str) -> Result<[u8; 3], String> {
+    let digits = value.trim().trim_start_matches('#');
+    match hex::decode(digits) {
+        Ok(bytes) if bytes.len() == 3 => Ok([bytes[0], bytes[1], bytes[2]]),
+        _ => Err(format!("Invalid colour '{}': expected six hex digits", value)),
+    }
+}
+
+/// Collect the QR payload from whichever data source was given on the command line
+fn read_payload(args: &Args) -> Result<Vec<u8>, String> {
+    if let Some(data) = &args.data {
+        return Ok(data.as_bytes().to_vec());
+    }
+
+    if let Some(hex_data) = &args.data_hex {
+        let cleaned: String = hex_data.chars().filter(|c| !c.is_whitespace()).collect();
+        return hex::decode(cleaned).map_err(|e| format!("Invalid --data-hex value: {}", e));
+    }
+
+    if let Some(b64_data) = &args.data_base64 {
+        return base64::engine::general_purpose::STANDARD
+            .decode(b64_data.trim())
+            .map_err(|e| format!("Invalid --data-base64 value: {}", e));
+    }
+
+    if let Some(path) = &args.data_file {
+        let mut buffer = Vec::new();
+        if path.as_os_str() == "-" {
+            std::io::stdin()
+                .read_to_end(&mut buffer)
+                .map_err(|e| format!("Failed to read data from stdin: {}", e))?;
+        } else {
+            buffer = std::fs::read(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        }
+        return Ok(buffer);
+    }
+
+    Err("No QR data given: use --data, --data-file, --data-hex or --data-base64".to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -133,4 +860,120 @@ mod tests {
         use clap::CommandFactory;
         Args::command().debug_assert();
     }
+
+    #[test]
+    fn test_binary_payload_sources() {
+        let args = Args::parse_from(["qr", "-k", "nature", "--data-hex", "00ff 80c3"]);
+        assert_eq!(read_payload(&args).unwrap(), vec![0x00, 0xff, 0x80, 0xc3]);
+
+        let args = Args::parse_from(["qr", "-k", "nature", "--data-base64", "AP+Aww=="]);
+        assert_eq!(read_payload(&args).unwrap(), vec![0x00, 0xff, 0x80, 0xc3]);
+
+        let args = Args::try_parse_from(["qr", "-k", "nature", "-d", "a", "--data-hex", "00"]);
+        assert!(args.is_err());
+    }
+
+    #[test]
+    fn test_flags_override_config() {
+        let args = Args::parse_from([
+            "qr", "-k", "sea", "-d", "x", "--width", "800", "--position", "center",
+            "--decoders", "rqrr",
+        ]);
+        let config = build_config_from(Config::default(), &args).unwrap();
+        assert_eq!(config.image_width, 800);
+        assert_eq!(config.qr_position, QrPosition::Center);
+        assert_eq!(config.decoders, vec![DecoderBackend::Rqrr]);
+
+        let args = Args::parse_from(["qr", "-k", "sea", "-d", "x", "--qr-size", "0.9"]);
+        assert!(build_config_from(Config::default(), &args).is_err());
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#1A237e"), Ok([0x1a, 0x23, 0x7e]));
+        assert_eq!(parse_color("ffffff"), Ok([255, 255, 255]));
+        assert!(parse_color("fff").is_err());
+        assert!(parse_color("zzzzzz").is_err());
+    }
+
+    #[test]
+    fn test_decode_subcommand() {
+        let args = Args::parse_from(["qr", "decode", "a.png", "b.jpg", "--format", "json"]);
+        match args.command {
+            Some(Command::Decode {
+                images,
+                format,
+                metadata_fallback,
+            }) => {
+                assert_eq!(images, vec![PathBuf::from("a.png"), PathBuf::from("b.jpg")]);
+                assert_eq!(format, "json");
+                assert!(!metadata_fallback);
+            }
+            _ => panic!("decode subcommand not parsed"),
+        }
+
+        assert!(Args::try_parse_from(["qr", "decode"]).is_err());
+
+        let args = Args::parse_from(["qr", "config", "show", "--config", "custom.yaml"]);
+        assert_eq!(args.config, Some(PathBuf::from("custom.yaml")));
+        assert!(matches!(
+            args.command,
+            Some(Command::Config {
+                action: ConfigAction::Show { .. }
+            })
+        ));
+        assert!(Args::try_parse_from(["qr", "-d", "x"]).is_err());
+
+        let args = Args::parse_from(["qr", "--width", "800", "--preset", "a4-flyer", "config", "show"]);
+        let config = build_config_from(Config::default(), &args).unwrap();
+        assert_eq!(config.image_width, 800);
+        assert_eq!(config.dpi, Some(300));
+    }
+
+    #[test]
+    fn test_preset_then_flags() {
+        let args = Args::parse_from([
+            "qr", "-k", "sea", "-d", "x", "--preset", "instagram-story", "--qr-size", "0.2",
+        ]);
+        let config = build_config_from(Config::default(), &args).unwrap();
+        assert_eq!((config.image_width, config.image_height), (1080, 1920));
+        assert_eq!(config.qr_size_ratio, 0.2);
+        assert_eq!(config.dpi, Some(72));
+
+        let args = Args::parse_from(["qr", "-k", "sea", "-d", "x", "--preset", "billboard"]);
+        assert!(build_config_from(Config::default(), &args).is_err());
+        assert!(matches!(
+            Args::parse_from(["qr", "presets"]).command,
+            Some(Command::Presets)
+        ));
+    }
+
+    #[test]
+    fn test_batch_subcommand() {
+        match Args::parse_from(["qr", "batch", "codes.csv", "--summary", "out.json", "-j", "8"])
+            .command
+        {
+            Some(Command::Batch {
+                manifest,
+                summary,
+                jobs,
+            }) => {
+                assert_eq!(manifest, PathBuf::from("codes.csv"));
+                assert_eq!(summary, Some(PathBuf::from("out.json")));
+                assert_eq!(jobs, Some(8));
+            }
+            _ => panic!("batch subcommand not parsed"),
+        }
+    }
+
+    #[test]
+    fn test_serve_subcommand() {
+        match Args::parse_from(["qr", "serve", "--bind", "0.0.0.0:9000", "--workers", "2"]).command {
+            Some(Command::Serve { bind, workers, .. }) => {
+                assert_eq!(bind, "0.0.0.0:9000");
+                assert_eq!(workers, Some(2));
+            }
+            _ => panic!("serve subcommand not parsed"),
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,301 @@
+use crate::error::Result;
+use crate::image_provider::BackgroundInfo;
+use crate::qr_validator::DecodedCode;
+use base64::Engine;
+use std::io::{Cursor, Write};
+use std::path::Path;
+
+/// PNG keywords; `Software` is one of the registered ones
+const PNG_SOFTWARE: &str = "Software";
+const PNG_PAYLOAD: &str = "QR-Payload";
+const PNG_PAYLOAD_BASE64: &str = "QR-Payload-Base64";
+const PNG_CREDIT: &str = "Credit";
+
+/// Identifies an XMP packet in a JPEG APP1 segment
+const JPEG_XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const XMP_NAMESPACE: &str = "urn:qr-code-generator:xmp:1.0/";
+
+/// What a saved image says about itself: the data in its code, the tool that
+/// made it and the credit for its background photo
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageMetadata {
+    /// Data encoded in the QR code
+    pub payload: Vec<u8>,
+    /// Crate name and version
+    pub generator: String,
+    /// Credit for the background photo
+    pub attribution: Option<String>,
+}
+
+impl ImageMetadata {
+    pub fn new(payload: &[u8], background: &BackgroundInfo) -> Self {
+        Self {
+            payload: payload.to_vec(),
+            generator: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            attribution: background.attribution.clone(),
+        }
+    }
+
+    /// Payload as text, if it is UTF-8 without control characters that XML
+    /// cannot carry; anything else is stored as base64
+    pub fn payload_text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.payload)
+            .ok()
+            .filter(|text| !text.chars().any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r')))
+    }
+
+    /// Whether codes found in the image carry this payload, in one code or
+    /// as a Structured Append sequence
+    pub fn matches(&self, codes: &[DecodedCode]) -> bool {
+        if codes.iter().any(|code| code.data == self.payload) {
+            return true;
+        }
+        let mut pieces: Vec<&DecodedCode> = codes
+            .iter()
+            .filter(|code| code.structured_append.is_some())
+            .collect();
+        pieces.sort_by_key(|code| code.structured_append.map(|header| header.index));
+        !pieces.is_empty()
+            && pieces
+                .iter()
+                .flat_map(|code| code.data.iter())
+                .eq(self.payload.iter())
+    }
+
+    /// JSON for `decode` output: the recorded payload (`text` and `hex`),
+    /// generator and photo credit
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "text": std::str::from_utf8(&self.payload).ok(),
+            "hex": hex::encode(&self.payload),
+            "generator": self.generator,
+            "attribution": self.attribution,
+        })
+    }
+
+    /// Add tEXt/iTXt chunks to a PNG before its header is written
+    pub(crate) fn add_to_png<W: Write>(&self, encoder: &mut png::Encoder<W>) -> Result<()> {
+        let add = |encoder: &mut png::Encoder<W>, keyword: &str, text: String, utf8: bool| {
+            if utf8 {
+                encoder.add_itxt_chunk(keyword.to_string(), text)
+            } else {
+                encoder.add_text_chunk(keyword.to_string(), text)
+            }
+            .map_err(std::io::Error::other)
+        };
+
+        add(encoder, PNG_SOFTWARE, self.generator.clone(), false)?;
+        match self.payload_text() {
+            Some(text) => add(encoder, PNG_PAYLOAD, text.to_string(), true)?,
+            None => add(encoder, PNG_PAYLOAD_BASE64, self.payload_base64(), false)?,
+        }
+        if let Some(attribution) = &self.attribution {
+            add(encoder, PNG_CREDIT, attribution.clone(), true)?;
+        }
+        Ok(())
+    }
+
+    /// Insert an XMP packet into an encoded JPEG, after its JFIF header.
+    /// Packets too large for one APP1 segment are left out.
+    pub(crate) fn add_to_jpeg(&self, jpeg: &mut Vec<u8>) {
+        let segment_len = 2 + JPEG_XMP_HEADER.len() + self.to_xmp().len();
+        if segment_len > u16::MAX as usize || !jpeg.starts_with(&[0xFF, 0xD8]) {
+            log::warn!("⚠ Metadata does not fit in a JPEG segment; not embedded");
+            return;
+        }
+
+        let mut position = 2;
+        if jpeg.get(2..4) == Some(&[0xFF, 0xE0]) {
+            position += 2 + u16::from_be_bytes([jpeg[4], jpeg[5]]) as usize;
+        }
+        let mut segment = vec![0xFF, 0xE1];
+        segment.extend_from_slice(&(segment_len as u16).to_be_bytes());
+        segment.extend_from_slice(JPEG_XMP_HEADER);
+        segment.extend_from_slice(self.to_xmp().as_bytes());
+        jpeg.splice(position..position, segment);
+    }
+
+    /// XMP packet: `xmp:CreatorTool`, `photoshop:Credit` and the payload
+    pub(crate) fn to_xmp(&self) -> String {
+        let payload = match self.payload_text() {
+            Some(text) => format!("<qrgen:Payload>{}</qrgen:Payload>", xml_escape(text)),
+            None => format!("<qrgen:PayloadBase64>{}</qrgen:PayloadBase64>", self.payload_base64()),
+        };
+        let credit = self
+            .attribution
+            .as_ref()
+            .map(|credit| format!("<photoshop:Credit>{}</photoshop:Credit>", xml_escape(credit)))
+            .unwrap_or_default();
+        format!(
+            concat!(
+                "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>",
+                "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">",
+                "<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">",
+                "<rdf:Description rdf:about=\"\" xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\" ",
+                "xmlns:photoshop=\"http://ns.adobe.com/photoshop/1.0/\" xmlns:qrgen=\"{ns}\">",
+                "<xmp:CreatorTool>{tool}</xmp:CreatorTool>{credit}{payload}",
+                "</rdf:Description></rdf:RDF></x:xmpmeta><?xpacket end=\"r\"?>"
+            ),
+            ns = XMP_NAMESPACE,
+            tool = xml_escape(&self.generator),
+            credit = credit,
+            payload = payload
+        )
+    }
+
+    fn from_xmp(xmp: &str) -> Option<Self> {
+        let payload = match xml_element(xmp, "qrgen:Payload") {
+            Some(text) => text.into_bytes(),
+            None => decode_base64(&xml_element(xmp, "qrgen:PayloadBase64")?)?,
+        };
+        Some(Self {
+            payload,
+            generator: xml_element(xmp, "xmp:CreatorTool").unwrap_or_default(),
+            attribution: xml_element(xmp, "photoshop:Credit"),
+        })
+    }
+
+    fn from_png(bytes: &[u8]) -> Option<Self> {
+        let reader = png::Decoder::new(Cursor::new(bytes)).read_info().ok()?;
+        let info = reader.info();
+        let mut text: Vec<(String, String)> = info
+            .uncompressed_latin1_text
+            .iter()
+            .map(|chunk| (chunk.keyword.clone(), chunk.text.clone()))
+            .collect();
+        text.extend(
+            info.utf8_text
+                .iter()
+                .filter_map(|chunk| Some((chunk.keyword.clone(), chunk.get_text().ok()?))),
+        );
+        let find = |keyword: &str| {
+            text.iter()
+                .find(|(k, _)| k == keyword)
+                .map(|(_, value)| value.clone())
+        };
+
+        let payload = match find(PNG_PAYLOAD) {
+            Some(text) => text.into_bytes(),
+            None => decode_base64(&find(PNG_PAYLOAD_BASE64)?)?,
+        };
+        Some(Self {
+            payload,
+            generator: find(PNG_SOFTWARE).unwrap_or_default(),
+            attribution: find(PNG_CREDIT),
+        })
+    }
+
+    fn from_jpeg(bytes: &[u8]) -> Option<Self> {
+        let mut position = 2;
+        while let Some(&[0xFF, marker, high, low]) = bytes.get(position..position + 4) {
+            // Metadata comes before the image data (start of scan)
+            if marker == 0xDA {
+                break;
+            }
+            let end = position + 2 + u16::from_be_bytes([high, low]) as usize;
+            let segment = bytes.get(position + 4..end)?;
+            if marker == 0xE1 {
+                if let Some(xmp) = segment.strip_prefix(JPEG_XMP_HEADER) {
+                    return Self::from_xmp(&String::from_utf8_lossy(xmp));
+                }
+            }
+            position = end;
+        }
+        None
+    }
+
+    fn from_webp(bytes: &[u8]) -> Option<Self> {
+        let mut decoder = image_webp::WebPDecoder::new(Cursor::new(bytes)).ok()?;
+        let xmp = decoder.xmp_metadata().ok()??;
+        Self::from_xmp(&String::from_utf8_lossy(&xmp))
+    }
+
+    fn payload_base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(&self.payload)
+    }
+}
+
+/// Metadata embedded in an encoded PNG, JPEG or WebP image, if any
+pub fn read(bytes: &[u8]) -> Option<ImageMetadata> {
+    if bytes.starts_with(b"\x89PNG") {
+        ImageMetadata::from_png(bytes)
+    } else if bytes.starts_with(&[0xFF, 0xD8]) {
+        ImageMetadata::from_jpeg(bytes)
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        ImageMetadata::from_webp(bytes)
+    } else {
+        None
+    }
+}
+
+/// [`read`] from a file
+pub fn read_file(path: &Path) -> Result<Option<ImageMetadata>> {
+    Ok(read(&std::fs::read(path)?))
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    base64::engine::general_purpose::STANDARD.decode(text.trim()).ok()
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Text of the first `<tag>...</tag>` element
+fn xml_element(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    Some(xml_unescape(&xml[start..end]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output;
+    use image::DynamicImage;
+
+    fn metadata(payload: &[u8]) -> ImageMetadata {
+        ImageMetadata {
+            payload: payload.to_vec(),
+            generator: "qr_code_generator 0.1.0".to_string(),
+            attribution: Some("Photo by Zoë <Z> on Unsplash".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_round_trip_png_jpeg_webp() {
+        let image = DynamicImage::new_rgb8(8, 8);
+        let dir = std::env::temp_dir().join(format!("qrgen-metadata-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for (name, payload) in [
+            ("text.png", b"https://example.com/?a=1&b=<2>".to_vec()),
+            ("binary.png", vec![0, 159, 146, 150]),
+            ("text.jpg", b"https://example.com/?a=1&b=<2>".to_vec()),
+            ("binary.jpg", vec![0, 159, 146, 150]),
+            ("text.webp", "안녕하세요".as_bytes().to_vec()),
+        ] {
+            let path = dir.join(name);
+            let expected = metadata(&payload);
+            output::save_with_metadata(&image, &path, Some(300), Some(&expected)).unwrap();
+            assert_eq!(read_file(&path).unwrap(), Some(expected), "{}", name);
+            assert!(image::open(&path).is_ok(), "{}", name);
+        }
+
+        output::save(&image, &dir.join("plain.png"), None).unwrap();
+        assert_eq!(read_file(&dir.join("plain.png")).unwrap(), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
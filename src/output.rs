@@ -0,0 +1,216 @@
+use crate::error::{QrImageError, Result};
+use crate::metadata::ImageMetadata;
+use base64::Engine;
+use image::codecs::jpeg::{JpegEncoder, PixelDensity};
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const METRES_PER_INCH: f64 = 0.0254;
+
+/// Encoding for a generated image
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    Jpeg,
+    /// Vector code over the background embedded as a PNG, see [`encode_svg`]
+    Svg,
+}
+
+impl OutputFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "image/png",
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Svg => "image/svg+xml",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Svg => "svg",
+        }
+    }
+}
+
+/// Encode an image in memory, recording `dpi` where the format allows
+pub fn encode(image: &DynamicImage, format: OutputFormat, dpi: Option<u32>) -> Result<Vec<u8>> {
+    encode_with_metadata(image, format, dpi, None)
+}
+
+/// [`encode`], also embedding `metadata`: text chunks for PNG, XMP for JPEG.
+/// A finished raster cannot become an SVG; encode a
+/// [`GenerationResult`](crate::request::GenerationResult) for that.
+pub fn encode_with_metadata(
+    image: &DynamicImage,
+    format: OutputFormat,
+    dpi: Option<u32>,
+    metadata: Option<&ImageMetadata>,
+) -> Result<Vec<u8>> {
+    match format {
+        OutputFormat::Png => {
+            let mut bytes = Vec::new();
+            write_png(image, &mut bytes, dpi, metadata)?;
+            Ok(bytes)
+        }
+        OutputFormat::Jpeg => encode_jpeg(image, dpi, metadata),
+        OutputFormat::Svg => Err(QrImageError::EncodingError(
+            "SVG output needs the code, not only the finished image".to_string(),
+        )),
+    }
+}
+
+/// SVG document of `background`, embedded as a PNG data URI, under the
+/// vector `layers`, with `metadata` as XMP in a `<metadata>` element
+pub fn encode_svg(
+    background: &DynamicImage,
+    layers: &str,
+    metadata: Option<&ImageMetadata>,
+) -> Result<Vec<u8>> {
+    let mut png = Vec::new();
+    write_png(background, &mut png, None, None)?;
+    let (width, height) = (background.width(), background.height());
+    Ok(format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            "{metadata}",
+            r#"<image width="{w}" height="{h}" href="data:image/png;base64,{data}"/>"#,
+            "{layers}</svg>"
+        ),
+        w = width,
+        h = height,
+        metadata = metadata
+            .map(|metadata| format!("<metadata>{}</metadata>", metadata.to_xmp()))
+            .unwrap_or_default(),
+        data = base64::engine::general_purpose::STANDARD.encode(png),
+        layers = layers,
+    )
+    .into_bytes())
+}
+
+/// Save an image, recording `dpi` as its print resolution when given: a pHYs
+/// chunk for PNG, JFIF density for JPEG. Other formats are saved without it.
+pub fn save(image: &DynamicImage, path: &Path, dpi: Option<u32>) -> Result<()> {
+    save_with_metadata(image, path, dpi, None)
+}
+
+/// [`save`], also embedding `metadata`: tEXt/iTXt chunks for PNG, XMP for
+/// JPEG and WebP. Other formats are saved without it.
+pub fn save_with_metadata(
+    image: &DynamicImage,
+    path: &Path,
+    dpi: Option<u32>,
+    metadata: Option<&ImageMetadata>,
+) -> Result<()> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    match (dpi, metadata, extension.as_str()) {
+        (None, None, _) => Ok(image.save(path)?),
+        (_, _, "png") => {
+            let mut file = BufWriter::new(File::create(path)?);
+            write_png(image, &mut file, dpi, metadata)?;
+            file.into_inner().map_err(|e| e.into_error())?;
+            Ok(())
+        }
+        (_, _, "jpg" | "jpeg") => Ok(std::fs::write(path, encode_jpeg(image, dpi, metadata)?)?),
+        (_, Some(metadata), "webp") => Ok(std::fs::write(path, encode_webp(image, metadata)?)?),
+        _ => Ok(image.save(path)?),
+    }
+}
+
+fn encode_jpeg(image: &DynamicImage, dpi: Option<u32>, metadata: Option<&ImageMetadata>) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut encoder = JpegEncoder::new(&mut bytes);
+    if let Some(dpi) = dpi {
+        encoder.set_pixel_density(PixelDensity::dpi(dpi.min(u16::MAX as u32) as u16));
+    }
+    encoder.encode_image(&DynamicImage::ImageRgb8(image.to_rgb8()))?;
+    if let Some(metadata) = metadata {
+        metadata.add_to_jpeg(&mut bytes);
+    }
+    Ok(bytes)
+}
+
+/// Lossless WebP with an XMP chunk
+fn encode_webp(image: &DynamicImage, metadata: &ImageMetadata) -> Result<Vec<u8>> {
+    let rgba = image.to_rgba8();
+    let mut bytes = Vec::new();
+    let mut encoder = image_webp::WebPEncoder::new(&mut bytes);
+    encoder.set_xmp_metadata(metadata.to_xmp().into_bytes());
+    encoder
+        .encode(rgba.as_raw(), rgba.width(), rgba.height(), image_webp::ColorType::Rgba8)
+        .map_err(std::io::Error::other)?;
+    Ok(bytes)
+}
+
+/// Encode an image as PNG in memory, with a pHYs chunk when `dpi` is given
+pub fn encode_png(image: &DynamicImage, dpi: Option<u32>) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    write_png(image, &mut bytes, dpi, None)?;
+    Ok(bytes)
+}
+
+fn write_png<W: Write>(
+    image: &DynamicImage,
+    writer: W,
+    dpi: Option<u32>,
+    metadata: Option<&ImageMetadata>,
+) -> Result<()> {
+    let rgba = image.to_rgba8();
+    let mut encoder = png::Encoder::new(writer, rgba.width(), rgba.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    if let Some(dpi) = dpi {
+        let dots_per_metre = (dpi as f64 / METRES_PER_INCH).round() as u32;
+        encoder.set_pixel_dims(Some(png::PixelDimensions {
+            xppu: dots_per_metre,
+            yppu: dots_per_metre,
+            unit: png::Unit::Meter,
+        }));
+    }
+    if let Some(metadata) = metadata {
+        metadata.add_to_png(&mut encoder)?;
+    }
+
+    encoder
+        .write_header()
+        .and_then(|mut writer| {
+            writer.write_image_data(rgba.as_raw())?;
+            writer.finish()
+        })
+        .map_err(std::io::Error::other)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_png_records_dpi() {
+        let path = std::env::temp_dir().join(format!("qrgen-dpi-{}.png", std::process::id()));
+        let image = DynamicImage::new_rgb8(4, 3);
+        save(&image, &path, Some(300)).unwrap();
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let reader = decoder.read_info().unwrap();
+        let dims = reader.info().pixel_dims.unwrap();
+        assert_eq!(dims.unit, png::Unit::Meter);
+        assert_eq!(dims.xppu, 11811);
+        assert_eq!((reader.info().width, reader.info().height), (4, 3));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,341 @@
+use crate::binarize;
+use image::{imageops, GrayImage};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// One preprocessing strategy applied to the grayscale image before a
+/// decode attempt. The validator tries its configured list in order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PreprocessStep {
+    /// Decode the image as is
+    Identity,
+    /// Stretch the darkest and brightest pixels to 0 and 255
+    ContrastStretch,
+    /// Global histogram equalisation
+    HistogramEqualization,
+    /// Contrast-limited adaptive histogram equalisation over a grid of tiles
+    Clahe { tiles: u32, clip_limit: f32 },
+    /// Mean-C adaptive threshold
+    AdaptiveThreshold { window: u32, offset: i32 },
+    /// Otsu global threshold
+    Otsu,
+    /// Sauvola local threshold
+    Sauvola { window: u32, k: f32 },
+    /// 3×3 Laplacian sharpening
+    Sharpen,
+    /// Resize by this factor
+    Downscale(f32),
+    /// Crop to the densest high-contrast region, where the code most likely is
+    CropToQrRegion,
+    /// Swap dark and light, for light-on-dark codes
+    Invert,
+    /// Shift every pixel's brightness by this amount
+    Brightness(i32),
+}
+
+impl PreprocessStep {
+    /// Strategies tried by default, in order
+    pub fn default_pipeline() -> Vec<PreprocessStep> {
+        vec![
+            PreprocessStep::Identity,
+            PreprocessStep::ContrastStretch,
+            PreprocessStep::AdaptiveThreshold {
+                window: binarize::DEFAULT_WINDOW,
+                offset: binarize::DEFAULT_OFFSET,
+            },
+            PreprocessStep::Otsu,
+            PreprocessStep::Sauvola {
+                window: binarize::DEFAULT_WINDOW,
+                k: binarize::DEFAULT_SAUVOLA_K,
+            },
+        ]
+    }
+
+    /// The same step with a different threshold window and offset; steps
+    /// other than adaptive and Sauvola thresholding are unchanged
+    pub fn with_threshold(self, window: u32, offset: i32) -> Self {
+        match self {
+            PreprocessStep::AdaptiveThreshold { .. } => {
+                PreprocessStep::AdaptiveThreshold { window, offset }
+            }
+            PreprocessStep::Sauvola { k, .. } => PreprocessStep::Sauvola { window, k },
+            step => step,
+        }
+    }
+
+    /// Apply the step, returning the processed image
+    pub fn apply(&self, image: &GrayImage) -> GrayImage {
+        let mut gray = image.clone();
+        match *self {
+            PreprocessStep::Identity => {}
+            PreprocessStep::ContrastStretch => contrast_stretch(&mut gray),
+            PreprocessStep::HistogramEqualization => {
+                imageproc::contrast::equalize_histogram_mut(&mut gray)
+            }
+            PreprocessStep::Clahe { tiles, clip_limit } => clahe(&mut gray, tiles, clip_limit),
+            PreprocessStep::AdaptiveThreshold { window, offset } => {
+                binarize::adaptive_threshold(&mut gray, window, offset)
+            }
+            PreprocessStep::Otsu => binarize::otsu_threshold(&mut gray),
+            PreprocessStep::Sauvola { window, k } => {
+                binarize::sauvola_threshold(&mut gray, window, k)
+            }
+            PreprocessStep::Sharpen => gray = imageproc::filter::sharpen3x3(&gray),
+            PreprocessStep::Downscale(factor) => {
+                let width = ((gray.width() as f32 * factor) as u32).max(1);
+                let height = ((gray.height() as f32 * factor) as u32).max(1);
+                gray = imageops::resize(&gray, width, height, imageops::FilterType::Triangle);
+            }
+            PreprocessStep::CropToQrRegion => {
+                if let Some((x, y, width, height)) = find_code_region(&gray) {
+                    gray = imageops::crop_imm(&gray, x, y, width, height).to_image();
+                }
+            }
+            PreprocessStep::Invert => imageops::invert(&mut gray),
+            PreprocessStep::Brightness(delta) => gray = imageops::brighten(&gray, delta),
+        }
+        gray
+    }
+
+    /// Map a point in the processed image back to the source image, undoing
+    /// any resizing or cropping this step performed
+    pub fn source_point(&self, source: &GrayImage, (x, y): (i32, i32)) -> (i32, i32) {
+        match *self {
+            PreprocessStep::Downscale(factor) => {
+                let scale_x = source.width() as f32 / ((source.width() as f32 * factor) as u32).max(1) as f32;
+                let scale_y = source.height() as f32 / ((source.height() as f32 * factor) as u32).max(1) as f32;
+                ((x as f32 * scale_x) as i32, (y as f32 * scale_y) as i32)
+            }
+            PreprocessStep::CropToQrRegion => match find_code_region(source) {
+                Some((left, top, _, _)) => (x + left as i32, y + top as i32),
+                None => (x, y),
+            },
+            _ => (x, y),
+        }
+    }
+}
+
+impl fmt::Display for PreprocessStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreprocessStep::Identity => write!(f, "original image"),
+            PreprocessStep::ContrastStretch => write!(f, "contrast stretch"),
+            PreprocessStep::HistogramEqualization => write!(f, "histogram equalisation"),
+            PreprocessStep::Clahe { tiles, clip_limit } => {
+                write!(f, "CLAHE ({}x{} tiles, clip {})", tiles, tiles, clip_limit)
+            }
+            PreprocessStep::AdaptiveThreshold { window, offset } => {
+                write!(f, "adaptive threshold (window {}, C {})", window, offset)
+            }
+            PreprocessStep::Otsu => write!(f, "Otsu threshold"),
+            PreprocessStep::Sauvola { window, k } => {
+                write!(f, "Sauvola threshold (window {}, k {})", window, k)
+            }
+            PreprocessStep::Sharpen => write!(f, "sharpen"),
+            PreprocessStep::Downscale(factor) => write!(f, "downscale x{}", factor),
+            PreprocessStep::CropToQrRegion => write!(f, "crop to QR region"),
+            PreprocessStep::Invert => write!(f, "invert"),
+            PreprocessStep::Brightness(delta) => write!(f, "brightness {:+}", delta),
+        }
+    }
+}
+
+fn contrast_stretch(image: &mut GrayImage) {
+    let min_val = image.pixels().map(|p| p[0]).min().unwrap_or(0);
+    let max_val = image.pixels().map(|p| p[0]).max().unwrap_or(255);
+
+    if max_val > min_val {
+        let range = (max_val - min_val) as f32;
+        for pixel in image.pixels_mut() {
+            pixel[0] = ((pixel[0] - min_val) as f32 / range * 255.0) as u8;
+        }
+    }
+}
+
+/// Contrast-limited adaptive histogram equalisation: each tile gets its own
+/// clipped equalisation curve, blended bilinearly between tile centres
+fn clahe(image: &mut GrayImage, tiles: u32, clip_limit: f32) {
+    let (width, height) = image.dimensions();
+    let tiles = tiles.clamp(1, width.min(height).max(1));
+    let tile_w = width.div_ceil(tiles);
+    let tile_h = height.div_ceil(tiles);
+
+    let mut curves = Vec::with_capacity((tiles * tiles) as usize);
+    for ty in 0..tiles {
+        for tx in 0..tiles {
+            let mut histogram = [0u32; 256];
+            let mut count = 0u32;
+            for y in (ty * tile_h)..((ty + 1) * tile_h).min(height) {
+                for x in (tx * tile_w)..((tx + 1) * tile_w).min(width) {
+                    histogram[image.get_pixel(x, y)[0] as usize] += 1;
+                    count += 1;
+                }
+            }
+            curves.push(clipped_curve(&mut histogram, count, clip_limit));
+        }
+    }
+
+    // Interpolate between the curves of the four nearest tile centres
+    let source = image.clone();
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let value = source.get_pixel(x, y)[0] as usize;
+        let gx = ((x as f32 + 0.5) / tile_w as f32 - 0.5).clamp(0.0, (tiles - 1) as f32);
+        let gy = ((y as f32 + 0.5) / tile_h as f32 - 0.5).clamp(0.0, (tiles - 1) as f32);
+        let (x0, y0) = (gx.floor() as u32, gy.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(tiles - 1), (y0 + 1).min(tiles - 1));
+        let (fx, fy) = (gx - x0 as f32, gy - y0 as f32);
+
+        let curve = |tx: u32, ty: u32| curves[(ty * tiles + tx) as usize][value];
+        let top = curve(x0, y0) * (1.0 - fx) + curve(x1, y0) * fx;
+        let bottom = curve(x0, y1) * (1.0 - fx) + curve(x1, y1) * fx;
+        pixel[0] = (top * (1.0 - fy) + bottom * fy).round().clamp(0.0, 255.0) as u8;
+    }
+}
+
+/// Equalisation curve for a tile histogram, with counts above
+/// `clip_limit` times the mean bin redistributed evenly
+fn clipped_curve(histogram: &mut [u32; 256], count: u32, clip_limit: f32) -> [f32; 256] {
+    let limit = ((clip_limit * count as f32 / 256.0) as u32).max(1);
+    let mut excess = 0u32;
+    for bin in histogram.iter_mut() {
+        if *bin > limit {
+            excess += *bin - limit;
+            *bin = limit;
+        }
+    }
+    let bonus = excess / 256;
+    for bin in histogram.iter_mut() {
+        *bin += bonus;
+    }
+
+    let mut curve = [0f32; 256];
+    let mut cumulative = 0u32;
+    for (level, &bin) in histogram.iter().enumerate() {
+        cumulative += bin;
+        curve[level] = cumulative as f32 * 255.0 / count.max(1) as f32;
+    }
+    curve
+}
+
+/// Block size used when looking for the code region, in pixels
+const REGION_BLOCK: u32 = 16;
+
+/// Bounding box (x, y, width, height) of the largest cluster of blocks with
+/// dense strong edges, padded by one block. QR modules produce far more
+/// sharp transitions per area than typical photographs.
+fn find_code_region(image: &GrayImage) -> Option<(u32, u32, u32, u32)> {
+    let (width, height) = image.dimensions();
+    let (cols, rows) = (width / REGION_BLOCK, height / REGION_BLOCK);
+    if cols == 0 || rows == 0 {
+        return None;
+    }
+
+    let mut dense = vec![false; (cols * rows) as usize];
+    for by in 0..rows {
+        for bx in 0..cols {
+            let mut edges = 0u32;
+            for y in by * REGION_BLOCK..(by + 1) * REGION_BLOCK {
+                for x in bx * REGION_BLOCK..(bx + 1) * REGION_BLOCK {
+                    let here = image.get_pixel(x, y)[0] as i32;
+                    let right = image.get_pixel((x + 1).min(width - 1), y)[0] as i32;
+                    let below = image.get_pixel(x, (y + 1).min(height - 1))[0] as i32;
+                    if (here - right).abs() + (here - below).abs() > 96 {
+                        edges += 1;
+                    }
+                }
+            }
+            dense[(by * cols + bx) as usize] = edges * 8 > REGION_BLOCK * REGION_BLOCK;
+        }
+    }
+
+    // Largest 4-connected cluster of dense blocks
+    let mut seen = vec![false; dense.len()];
+    let mut best: Option<(usize, (u32, u32, u32, u32))> = None;
+    for start in 0..dense.len() {
+        if !dense[start] || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let mut stack = vec![start];
+        let mut size = 0;
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (cols, rows, 0, 0);
+        while let Some(index) = stack.pop() {
+            size += 1;
+            let (bx, by) = (index as u32 % cols, index as u32 / cols);
+            min_x = min_x.min(bx);
+            min_y = min_y.min(by);
+            max_x = max_x.max(bx);
+            max_y = max_y.max(by);
+
+            let neighbours = [
+                (bx > 0).then(|| index - 1),
+                (bx + 1 < cols).then(|| index + 1),
+                (by > 0).then(|| index - cols as usize),
+                (by + 1 < rows).then(|| index + cols as usize),
+            ];
+            for next in neighbours.into_iter().flatten() {
+                if dense[next] && !seen[next] {
+                    seen[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        if best.is_none_or(|(best_size, _)| size > best_size) {
+            best = Some((size, (min_x, min_y, max_x, max_y)));
+        }
+    }
+
+    let (_, (min_x, min_y, max_x, max_y)) = best?;
+    let x0 = min_x.saturating_sub(1) * REGION_BLOCK;
+    let y0 = min_y.saturating_sub(1) * REGION_BLOCK;
+    let x1 = ((max_x + 2) * REGION_BLOCK).min(width);
+    let y1 = ((max_y + 2) * REGION_BLOCK).min(height);
+    Some((x0, y0, x1 - x0, y1 - y0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    #[test]
+    fn test_steps_keep_range_and_size() {
+        let image = GrayImage::from_fn(64, 48, |x, y| Luma([(60 + x + y) as u8]));
+        for step in [
+            PreprocessStep::ContrastStretch,
+            PreprocessStep::HistogramEqualization,
+            PreprocessStep::Clahe {
+                tiles: 4,
+                clip_limit: 2.0,
+            },
+            PreprocessStep::Sharpen,
+            PreprocessStep::Invert,
+        ] {
+            let result = step.apply(&image);
+            assert_eq!(result.dimensions(), (64, 48), "{}", step);
+        }
+
+        let stretched = PreprocessStep::ContrastStretch.apply(&image);
+        assert_eq!(stretched.get_pixel(0, 0)[0], 0);
+        assert_eq!(stretched.get_pixel(63, 47)[0], 255);
+        assert_eq!(PreprocessStep::Invert.apply(&image).get_pixel(0, 0)[0], 195);
+    }
+
+    #[test]
+    fn test_crop_to_code_region() {
+        // Checkerboard "code" in a flat background
+        let image = GrayImage::from_fn(320, 240, |x, y| {
+            let inside = (160..240).contains(&x) && (96..176).contains(&y);
+            let dark = inside && ((x / 4) + (y / 4)) % 2 == 0;
+            Luma([if dark { 0 } else { 200 }])
+        });
+
+        let (x, y, width, height) = find_code_region(&image).unwrap();
+        assert!(x <= 160 && y <= 96);
+        assert!(x + width >= 240 && y + height >= 176);
+        assert!(width <= 128 && height <= 128);
+
+        let cropped = PreprocessStep::CropToQrRegion.apply(&image);
+        assert_eq!(cropped.dimensions(), (width, height));
+    }
+}
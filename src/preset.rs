@@ -0,0 +1,111 @@
+use crate::config::{Config, QrPosition};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Output dimensions and code placement for a common target
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    #[serde(default)]
+    pub description: String,
+    pub width: u32,
+    pub height: u32,
+    pub qr_size_ratio: f32,
+    pub position: QrPosition,
+    /// Print resolution recorded in the output file
+    pub dpi: u32,
+}
+
+impl Preset {
+    fn new(
+        description: &str,
+        (width, height): (u32, u32),
+        qr_size_ratio: f32,
+        position: QrPosition,
+        dpi: u32,
+    ) -> Self {
+        Self {
+            description: description.to_string(),
+            width,
+            height,
+            qr_size_ratio,
+            position,
+            dpi,
+        }
+    }
+
+    /// Presets shipped with the generator
+    pub fn builtin() -> BTreeMap<String, Preset> {
+        [
+            (
+                "instagram-post",
+                Preset::new("Instagram square post", (1080, 1080), 0.3, QrPosition::BottomRight, 72),
+            ),
+            (
+                "instagram-story",
+                Preset::new(
+                    "Instagram/Facebook story, clear of the top and bottom UI",
+                    (1080, 1920),
+                    0.4,
+                    QrPosition::Center,
+                    72,
+                ),
+            ),
+            (
+                "a4-flyer",
+                Preset::new("A4 portrait flyer at 300 DPI", (2480, 3508), 0.25, QrPosition::BottomRight, 300),
+            ),
+            (
+                "business-card",
+                Preset::new("3.5×2 in business card at 300 DPI", (1050, 600), 0.45, QrPosition::BottomRight, 300),
+            ),
+            (
+                "slide",
+                Preset::new("16:9 presentation slide", (1920, 1080), 0.3, QrPosition::BottomRight, 96),
+            ),
+        ]
+        .into_iter()
+        .map(|(name, preset)| (name.to_string(), preset))
+        .collect()
+    }
+
+    /// Built-in presets merged with those defined in the config; a user preset
+    /// replaces a built-in one of the same name
+    pub fn all(config: &Config) -> BTreeMap<String, Preset> {
+        let mut presets = Self::builtin();
+        presets.extend(config.presets.clone());
+        presets
+    }
+
+    /// Look up a preset by name
+    pub fn find(config: &Config, name: &str) -> Option<Preset> {
+        Self::all(config).remove(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_presets_extend_and_override() {
+        let mut config = Config::default();
+        config.presets.insert(
+            "slide".to_string(),
+            Preset::new("4:3 slide", (1024, 768), 0.3, QrPosition::TopRight, 96),
+        );
+        config.presets.insert(
+            "poster".to_string(),
+            Preset::new("", (3508, 4961), 0.2, QrPosition::BottomLeft, 300),
+        );
+
+        let all = Preset::all(&config);
+        assert!(all.contains_key("instagram-story"));
+        assert_eq!(all["slide"].width, 1024);
+
+        let poster = Preset::find(&config, "poster").unwrap();
+        let config = config.with_preset(&poster);
+        assert_eq!((config.image_width, config.image_height), (3508, 4961));
+        assert_eq!(config.qr_position, QrPosition::BottomLeft);
+        assert_eq!(config.dpi, Some(300));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::config::{Config, ErrorCorrection};
+use crate::error::Result;
+use crate::image_provider::BackgroundInfo;
+use crate::qr_validator::ValidationReport;
+use crate::repair::Adjustment;
+use crate::request::{GenerationResult, Placement};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Suffix appended to the output's file name for its sidecar, keeping the
+/// extension so `poster.png` and `poster.jpg` get separate sidecars
+pub const SIDECAR_SUFFIX: &str = ".provenance.json";
+
+/// Written in place of secrets in the config snapshot
+const REDACTED: &str = "<redacted>";
+
+/// How a generated image was produced, for auditing and reproducing it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Provenance {
+    /// Crate name and version that generated the image
+    pub generator: String,
+    /// Generation time, RFC 3339 in UTC
+    pub created_at: String,
+    /// Hash of the encoded payload; the payload itself is not stored
+    pub payload: PayloadDigest,
+    /// Symbol read back by the validator, when validation ran
+    pub code: Option<CodeInfo>,
+    pub placement: Placement,
+    /// Background source and photo attribution
+    pub background: BackgroundInfo,
+    /// Config the image was finally generated with, secrets redacted
+    pub config: Config,
+    pub validation: Option<ValidationReport>,
+    /// Repairs made to get the code past validation
+    pub adjustments: Vec<Adjustment>,
+    /// The saved file, once written
+    pub output: Option<OutputDigest>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PayloadDigest {
+    pub sha256: String,
+    pub bytes: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CodeInfo {
+    /// QR version (1-40)
+    pub version: u8,
+    pub ec_level: ErrorCorrection,
+    /// Mask pattern (0-7)
+    pub mask: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputDigest {
+    /// File name, relative to the sidecar
+    pub file: String,
+    pub sha256: String,
+    pub bytes: usize,
+}
+
+impl Provenance {
+    /// Record how `result` was made from `payload`
+    pub fn from_result(result: &GenerationResult, payload: &[u8]) -> Self {
+        let mut config = result.config.clone();
+        if config.unsplash_api_key.is_some() {
+            config.unsplash_api_key = Some(REDACTED.to_string());
+        }
+
+        Self {
+            generator: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            created_at: rfc3339_now(),
+            payload: PayloadDigest {
+                sha256: sha256_hex(payload),
+                bytes: payload.len(),
+            },
+            code: result.report.as_ref().map(|report| CodeInfo {
+                version: report.version,
+                ec_level: report.ec_level,
+                mask: report.mask,
+            }),
+            placement: result.placement.clone(),
+            background: result.background.clone(),
+            config,
+            validation: result.report.clone(),
+            adjustments: result.adjustments.clone(),
+            output: None,
+        }
+    }
+
+    /// Hash the saved image at `output` and write the sidecar next to it,
+    /// returning the sidecar's path
+    pub fn write_sidecar(mut self, output: &Path) -> Result<PathBuf> {
+        let bytes = std::fs::read(output)?;
+        self.output = Some(OutputDigest {
+            file: output
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            sha256: sha256_hex(&bytes),
+            bytes: bytes.len(),
+        });
+
+        let path = sidecar_path(output);
+        std::fs::write(&path, serde_json::to_string_pretty(&self)?)?;
+        Ok(path)
+    }
+}
+
+/// `poster.png` -> `poster.png.provenance.json`
+pub fn sidecar_path(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(SIDECAR_SUFFIX);
+    PathBuf::from(path)
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+fn rfc3339_now() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    rfc3339(seconds)
+}
+
+/// Format seconds since the Unix epoch as `YYYY-MM-DDTHH:MM:SSZ`
+fn rfc3339(seconds: u64) -> String {
+    let days = (seconds / 86_400) as i64;
+    let time = seconds % 86_400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::QrPosition;
+    use crate::metadata::ImageMetadata;
+    use crate::output::OutputFormat;
+    use image::DynamicImage;
+
+    fn result() -> GenerationResult {
+        let image = DynamicImage::new_rgb8(4, 4);
+        GenerationResult {
+            background: BackgroundInfo::supplied(&image),
+            metadata: ImageMetadata::new(b"abc", &BackgroundInfo::supplied(&image)),
+            background_image: image.clone(),
+            image,
+            placement: Placement {
+                position: QrPosition::BottomRight,
+                size_ratio: 0.3,
+                bounds: None,
+            },
+            report: None,
+            adjustments: Vec::new(),
+            format: OutputFormat::Png,
+            config: Config::default().with_api_key("secret".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_rfc3339() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(951_825_600), "2000-02-29T12:00:00Z");
+        assert_eq!(rfc3339(1_792_322_096), "2026-10-18T11:14:56Z");
+    }
+
+    #[test]
+    fn test_sidecar_hashes_payload_and_output() {
+        let dir = std::env::temp_dir().join(format!("qrgen-provenance-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("poster.png");
+        std::fs::write(&output, b"abc").unwrap();
+
+        let path = Provenance::from_result(&result(), b"abc").write_sidecar(&output).unwrap();
+        assert_eq!(path, dir.join("poster.png.provenance.json"));
+        assert_ne!(sidecar_path(&dir.join("poster.jpg")), path);
+
+        let written: Provenance =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let abc = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert_eq!(written.payload.sha256, abc);
+        assert_eq!(written.payload.bytes, 3);
+        let output = written.output.unwrap();
+        assert_eq!(output.file, "poster.png");
+        assert_eq!(output.sha256, abc);
+        assert_eq!(written.config.unsplash_api_key.as_deref(), Some(REDACTED));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
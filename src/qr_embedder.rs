@@ -1,8 +1,11 @@
-use crate::config::{Config, QrPosition};
-use crate::error::Result;
+use crate::config::{CodeType, Config, QrPosition};
+use crate::error::{QrImageError, Result};
+use crate::segments::{self, Segment};
+use crate::structured_append::AppendedSymbol;
 use image::{DynamicImage, Rgba, RgbaImage};
 use log::{debug, info};
-use qrcode::QrCode;
+use qrcode::render::{svg, Renderer};
+use qrcode::{Color, QrCode};
 
 pub struct QrEmbedder {
     config: Config,
@@ -15,22 +18,115 @@ impl QrEmbedder {
 
     /// Generate QR code and embed it into the background image
     pub fn embed_qr_code(&self, background: DynamicImage, data: &str) -> Result<DynamicImage> {
+        self.embed_qr_bytes(background, data.as_bytes())
+    }
+
+    /// Generate QR code from arbitrary bytes and embed it into the background image
+    pub fn embed_qr_bytes(&self, background: DynamicImage, data: &[u8]) -> Result<DynamicImage> {
         info!("Embedding QR code with data length: {}", data.len());
 
         // Generate QR code
-        let qr_code = QrCode::new(data.as_bytes())?;
-        debug!("QR code generated successfully");
+        let qr_code = self.build_code(data)?;
+        debug!("QR code generated successfully: {:?}", qr_code.version());
+
+        self.embed_code(background, &qr_code)
+    }
+
+    /// Generate QR code from explicit segments and embed it into the background image
+    pub fn embed_segments(
+        &self,
+        background: DynamicImage,
+        segments: &[Segment],
+    ) -> Result<DynamicImage> {
+        info!("Embedding QR code from {} segment(s)", segments.len());
+
+        let qr_code = segments::encode_segments(
+            segments,
+            self.config.code_type,
+            self.config.error_correction.into(),
+        )?;
+        debug!("QR code generated successfully: {:?}", qr_code.version());
+
+        self.embed_code(background, &qr_code)
+    }
+
+    /// Lay out a Structured Append sequence as a grid of codes on the background
+    pub fn embed_structured(
+        &self,
+        background: DynamicImage,
+        symbols: &[AppendedSymbol],
+    ) -> Result<DynamicImage> {
+        info!("Embedding {} Structured Append symbol(s)", symbols.len());
+
+        let count = symbols.len().max(1) as u32;
+        let columns = (count as f32).sqrt().ceil() as u32;
+        let rows = count.div_ceil(columns);
+
+        // Shrink cells so the whole grid fits inside the margins
+        let margin = 30u32;
+        let gap = 10u32;
+        let fit_width = background.width().saturating_sub(2 * margin + gap * (columns - 1)) / columns;
+        let fit_height = background.height().saturating_sub(2 * margin + gap * (rows - 1)) / rows;
+        let cell_size = self
+            .calculate_qr_size(&background)
+            .min(fit_width)
+            .min(fit_height);
+        debug!("Grid {}x{} with cell size {}", columns, rows, cell_size);
+
+        let tiles = symbols
+            .iter()
+            .map(|symbol| {
+                self.render_modules(
+                    symbol.colors(),
+                    symbol.width(),
+                    symbol.version.is_micro(),
+                    cell_size,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let stride = tiles.iter().map(|t| t.width()).max().unwrap_or(cell_size) + gap;
+
+        let grid_width = stride * columns - gap;
+        let grid_height = stride * rows - gap;
+        let (x, y) = self.calculate_position(&background, grid_width, grid_height);
+        debug!("Grid position: ({}, {})", x, y);
+
+        let mut canvas = background.to_rgba8();
+        for (i, tile) in tiles.iter().enumerate() {
+            let column = i as u32 % columns;
+            let row = i as u32 / columns;
+            blend_onto(&mut canvas, tile, x + column * stride, y + row * stride);
+        }
+        info!("Structured Append grid embedded successfully");
 
+        Ok(DynamicImage::ImageRgba8(canvas))
+    }
+
+    fn build_code(&self, data: &[u8]) -> Result<QrCode> {
+        let ec_level = self.config.error_correction.into();
+
+        match self.config.code_type {
+            CodeType::Qr => Ok(QrCode::with_error_correction_level(data, ec_level)?),
+            CodeType::MicroQr => segments::encode_segments(
+                &[Segment::Byte(data.to_vec())],
+                CodeType::MicroQr,
+                ec_level,
+            ),
+        }
+    }
+
+    fn embed_code(&self, background: DynamicImage, qr_code: &QrCode) -> Result<DynamicImage> {
         // Calculate QR code size
         let qr_size = self.calculate_qr_size(&background);
         debug!("QR code size: {}x{}", qr_size, qr_size);
 
         // Render QR code to image with padding and background
-        let qr_image = self.render_qr_code(&qr_code, qr_size)?;
+        let qr_image = self.render_qr_code(qr_code, qr_size)?;
         debug!("QR code rendered to image");
+        check_fits(qr_image.width(), &background)?;
 
         // Calculate position
-        let (x, y) = self.calculate_position(&background, &qr_image);
+        let (x, y) = self.calculate_position(&background, qr_image.width(), qr_image.height());
         debug!("QR code position: ({}, {})", x, y);
 
         // Overlay QR code onto background
@@ -40,6 +136,7 @@ impl QrEmbedder {
         Ok(result)
     }
 
+    #[allow(clippy::manual_clamp)]
     fn calculate_qr_size(&self, background: &DynamicImage) -> u32 {
         let min_dimension = background.width().min(background.height());
         let size = (min_dimension as f32 * self.config.qr_size_ratio) as u32;
@@ -49,40 +146,51 @@ impl QrEmbedder {
     }
 
     fn render_qr_code(&self, qr_code: &QrCode, target_size: u32) -> Result<RgbaImage> {
-        // Render QR code as a simple black and white image
-        let qr_raw = qr_code.render::<image::Luma<u8>>().build();
-
-        // Calculate padding (10% of target size)
-        let padding = (target_size as f32 * 0.1) as u32;
-        let qr_content_size = target_size - (padding * 2);
+        self.render_modules(
+            &qr_code.to_colors(),
+            qr_code.width(),
+            qr_code.version().is_micro(),
+            target_size,
+        )
+    }
 
-        // Resize QR code to fit within padded area
-        let qr_resized = image::imageops::resize(
-            &qr_raw,
-            qr_content_size,
-            qr_content_size,
-            image::imageops::FilterType::Nearest, // Use Nearest for crisp QR codes
-        );
+    fn render_modules(
+        &self,
+        colors: &[Color],
+        width: usize,
+        micro: bool,
+        target_size: u32,
+    ) -> Result<RgbaImage> {
+        let TileLayout {
+            padding,
+            quiet_zone,
+            module_size,
+            offset,
+        } = tile_layout(width, micro, target_size)?;
+        let qr_resized = Renderer::<image::Luma<u8>>::new(colors, width, quiet_zone)
+            .module_dimensions(module_size, module_size)
+            .build();
 
         // Create final image with white background and padding
         let mut qr_with_bg = RgbaImage::new(target_size, target_size);
 
-        // Fill with semi-transparent white background
+        // Fill with the semi-transparent light colour
+        let [lr, lg, lb] = self.config.light_color;
         for pixel in qr_with_bg.pixels_mut() {
-            *pixel = Rgba([255, 255, 255, self.config.qr_background_opacity]);
+            *pixel = Rgba([lr, lg, lb, self.config.qr_background_opacity]);
         }
 
         // Copy QR code to center with padding
         for (x, y, pixel) in qr_resized.enumerate_pixels() {
             let luminance = pixel[0];
             let alpha = if luminance < 128 { 255 } else { self.config.qr_background_opacity };
-            let color = if luminance < 128 { 0 } else { 255 };
+            let [r, g, b] = if luminance < 128 {
+                self.config.dark_color
+            } else {
+                self.config.light_color
+            };
 
-            qr_with_bg.put_pixel(
-                x + padding,
-                y + padding,
-                Rgba([color, color, color, alpha]),
-            );
+            qr_with_bg.put_pixel(x + offset, y + offset, Rgba([r, g, b, alpha]));
         }
 
         // Add a thin border for better visibility
@@ -91,6 +199,49 @@ impl QrEmbedder {
         Ok(qr_with_bg)
     }
 
+    /// The code as an SVG group: the tile and its border as rectangles and
+    /// the modules as a path, placed where [`embed_qr_bytes`](Self::embed_qr_bytes)
+    /// draws them on `background`
+    pub fn svg_tile(&self, background: &DynamicImage, data: &[u8]) -> Result<String> {
+        let qr_code = self.build_code(data)?;
+        let target_size = self.calculate_qr_size(background);
+        let layout = tile_layout(qr_code.width(), qr_code.version().is_micro(), target_size)?;
+        check_fits(target_size, background)?;
+        let (x, y) = self.calculate_position(background, target_size, target_size);
+
+        let dark = svg_color(self.config.dark_color);
+        let symbol = Renderer::<svg::Color>::new(&qr_code.to_colors(), qr_code.width(), layout.quiet_zone)
+            .module_dimensions(layout.module_size, layout.module_size)
+            .dark_color(svg::Color(&dark))
+            .light_color(svg::Color("none"))
+            .build();
+        let symbol = symbol.trim_start_matches(r#"<?xml version="1.0" standalone="yes"?>"#);
+
+        // Same layers as the raster tile: translucent light fill, border on
+        // its edge, opaque dark modules
+        let border = layout.padding / 2;
+        let opacity = self.config.qr_background_opacity as f32 / 255.0;
+        Ok(format!(
+            concat!(
+                r#"<g transform="translate({x} {y})">"#,
+                r#"<rect x="{border}" y="{border}" width="{inner}" height="{inner}" fill="{light}" fill-opacity="{opacity}"/>"#,
+                r#"<rect x="{half}" y="{half}" width="{edge}" height="{edge}" fill="none" stroke="rgb(200,200,200)" stroke-opacity="{opacity}" stroke-width="{border}"/>"#,
+                r#"<g transform="translate({offset} {offset})">{symbol}</g>"#,
+                r#"</g>"#
+            ),
+            x = x,
+            y = y,
+            border = border,
+            inner = target_size - 2 * border,
+            light = svg_color(self.config.light_color),
+            opacity = opacity,
+            half = border as f32 / 2.0,
+            edge = target_size as f32 - border as f32,
+            offset = layout.offset,
+            symbol = symbol,
+        ))
+    }
+
     fn add_border(&self, image: &mut RgbaImage, border_width: u32) {
         let (width, height) = image.dimensions();
         let border_color = Rgba([200, 200, 200, self.config.qr_background_opacity]);
@@ -120,11 +271,9 @@ impl QrEmbedder {
         }
     }
 
-    fn calculate_position(&self, background: &DynamicImage, qr_image: &RgbaImage) -> (u32, u32) {
+    fn calculate_position(&self, background: &DynamicImage, qr_width: u32, qr_height: u32) -> (u32, u32) {
         let bg_width = background.width();
         let bg_height = background.height();
-        let qr_width = qr_image.width();
-        let qr_height = qr_image.height();
 
         let margin = 30u32; // Margin from edges
 
@@ -151,20 +300,77 @@ impl QrEmbedder {
         y: u32,
     ) -> Result<DynamicImage> {
         let mut bg_rgba = background.to_rgba8();
+        blend_onto(&mut bg_rgba, &qr_image, x, y);
 
-        // Alpha blending
-        for (qr_x, qr_y, qr_pixel) in qr_image.enumerate_pixels() {
-            let bg_x = x + qr_x;
-            let bg_y = y + qr_y;
+        Ok(DynamicImage::ImageRgba8(bg_rgba))
+    }
+}
 
-            if bg_x < bg_rgba.width() && bg_y < bg_rgba.height() {
-                let bg_pixel = bg_rgba.get_pixel(bg_x, bg_y);
-                let blended = alpha_blend(*bg_pixel, *qr_pixel);
-                bg_rgba.put_pixel(bg_x, bg_y, blended);
-            }
-        }
+/// Padding, quiet zone and module size of a `target_size` tile
+struct TileLayout {
+    padding: u32,
+    quiet_zone: u32,
+    module_size: u32,
+    /// Distance from the tile's edge to the symbol, quiet zone included
+    offset: u32,
+}
 
-        Ok(DynamicImage::ImageRgba8(bg_rgba))
+fn tile_layout(width: usize, micro: bool, target_size: u32) -> Result<TileLayout> {
+    // Calculate padding (10% of target size)
+    let padding = (target_size as f32 * 0.1) as u32;
+    let qr_content_size = target_size - (padding * 2);
+
+    // Render with a whole number of pixels per module so every module is
+    // the same size. The renderer adds the quiet zone the symbol requires:
+    // 4 modules for QR, 2 for Micro QR.
+    let quiet_zone = if micro { 2 } else { 4 };
+    let total_modules = width as u32 + 2 * quiet_zone;
+    // A large version on a small canvas would need less than one pixel
+    // per module, which would crop or overlap the symbol
+    if total_modules > qr_content_size {
+        return Err(QrImageError::ConfigError(format!(
+            "QR code is {} modules wide with its quiet zone, more than the {} px code area of a {} px tile; \
+             use a larger image or qr_size_ratio, or a lower max_symbol_version",
+            total_modules, qr_content_size, target_size
+        )));
+    }
+    let module_size = qr_content_size / total_modules;
+    Ok(TileLayout {
+        padding,
+        quiet_zone,
+        module_size,
+        offset: padding + (qr_content_size - total_modules * module_size) / 2,
+    })
+}
+
+fn check_fits(tile_size: u32, background: &DynamicImage) -> Result<()> {
+    if tile_size > background.width() || tile_size > background.height() {
+        return Err(QrImageError::ConfigError(format!(
+            "{}x{} px QR code tile does not fit the {}x{} px background",
+            tile_size,
+            tile_size,
+            background.width(),
+            background.height()
+        )));
+    }
+    Ok(())
+}
+
+fn svg_color([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn blend_onto(canvas: &mut RgbaImage, tile: &RgbaImage, x: u32, y: u32) {
+    // Alpha blending
+    for (qr_x, qr_y, qr_pixel) in tile.enumerate_pixels() {
+        let bg_x = x + qr_x;
+        let bg_y = y + qr_y;
+
+        if bg_x < canvas.width() && bg_y < canvas.height() {
+            let bg_pixel = canvas.get_pixel(bg_x, bg_y);
+            let blended = alpha_blend(*bg_pixel, *qr_pixel);
+            canvas.put_pixel(bg_x, bg_y, blended);
+        }
     }
 }
 
@@ -202,6 +408,45 @@ mod tests {
     }
 
     #[test]
+    fn test_embed_binary_payload() {
+        let embedder = QrEmbedder::new(Config::default());
+        let background = DynamicImage::new_rgb8(400, 400);
+        let data = [0x00, 0xff, 0x80, 0xc3, 0x28];
+
+        let result = embedder.embed_qr_bytes(background, &data).unwrap();
+        assert_eq!((result.width(), result.height()), (400, 400));
+    }
+
+    #[test]
+    fn test_large_version_needs_room() {
+        let embedder = QrEmbedder::new(Config::default());
+        let data = vec![b'x'; 2000];
+
+        // 200 px tile, 160 px code area: too small for a version 37+ symbol
+        let small = embedder.embed_qr_bytes(DynamicImage::new_rgb8(400, 400), &data);
+        assert!(matches!(small, Err(QrImageError::ConfigError(_))), "{:?}", small.err());
+        assert!(embedder.embed_qr_bytes(DynamicImage::new_rgb8(1920, 1080), &data).is_ok());
+
+        let tiny = embedder.embed_qr_code(DynamicImage::new_rgb8(150, 150), "hello");
+        assert!(matches!(tiny, Err(QrImageError::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_micro_qr_code_type() {
+        let config = Config::default()
+            .with_code_type(CodeType::MicroQr)
+            .with_error_correction(crate::config::ErrorCorrection::Low);
+        let embedder = QrEmbedder::new(config);
+
+        let code = embedder.build_code(b"HW-0042").unwrap();
+        assert!(code.version().is_micro());
+
+        let too_long = [b'x'; 64];
+        assert!(embedder.build_code(&too_long).is_err());
+    }
+
+    #[test]
+    #[allow(clippy::manual_range_contains)]
     fn test_calculate_qr_size() {
         let config = Config::default();
         let embedder = QrEmbedder::new(config);
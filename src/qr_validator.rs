@@ -1,61 +1,845 @@
+use crate::binarize;
+use crate::color_simulation::{ColorCheck, ColorSimulation};
+use crate::config::{CodeType, ErrorCorrection};
 use crate::error::{QrImageError, Result};
-use image::{DynamicImage, GrayImage};
+use crate::legibility::{self, LegibilityReport};
+use crate::preprocess::PreprocessStep;
+use crate::robustness::{Degradation, DegradationResult, RobustnessReport};
+use crate::segments::{self, Segment};
+use crate::structured_append;
+use crate::symbol_decoder::{self, DecodedSymbol, StructuredAppendHeader};
+use image::{DynamicImage, GrayImage, Luma};
 use log::{debug, info, warn};
 use rqrr::PreparedImage;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+/// Outcome of validating a generated code
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationOutcome {
+    /// The code was decoded and matched the expected data
+    Verified(ValidationReport),
+    /// No decoder is available for the code type, so nothing was checked
+    Skipped { reason: String },
+}
+
+/// What the decoder saw when a code validated successfully
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// QR version (1-40) of the decoded symbol
+    pub version: u8,
+    /// Error correction level read from the format information
+    pub ec_level: ErrorCorrection,
+    /// Mask pattern (0-7)
+    pub mask: u8,
+    /// Number of QR grids detected in the image
+    pub grids_found: usize,
+    /// Corners of every detected grid in image pixels: top-left, top-right,
+    /// bottom-right, bottom-left
+    pub grid_corners: Vec<[(i32, i32); 4]>,
+    /// Index into `grid_corners` of the grid that decoded
+    pub decoded_grid: usize,
+    /// Preprocessing strategy that made the code decode
+    pub strategy: PreprocessStep,
+    /// Attempt number (1-based) at which the code decoded
+    pub attempt: usize,
+    /// Time spent decoding across all attempts, in milliseconds
+    pub decode_time_ms: f64,
+    /// Luminance difference between light and dark pixels in the code
+    /// region of the unprocessed image (0-1)
+    pub contrast: f32,
+    /// Whether the code still decodes under each colour-vision and print
+    /// simulation
+    pub color_checks: Vec<ColorCheck>,
+    /// Outcome per decoder when cross-validating with more than rqrr
+    pub decoder_checks: Vec<DecoderCheck>,
+}
+
+impl ValidationReport {
+    /// Pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// A code found in an arbitrary image
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecodedCode {
+    /// Raw payload bytes
+    pub data: Vec<u8>,
+    /// QR version (1-40)
+    pub version: u8,
+    pub ec_level: ErrorCorrection,
+    /// Mask pattern (0-7)
+    pub mask: u8,
+    /// Corners in image pixels: top-left, top-right, bottom-right, bottom-left
+    pub corners: [(i32, i32); 4],
+    /// Preprocessing strategy under which the code was first found
+    pub strategy: PreprocessStep,
+    /// Position in a linked sequence, for Structured Append symbols
+    pub structured_append: Option<StructuredAppendHeader>,
+}
+
+impl DecodedCode {
+    /// Payload as text, if it is valid UTF-8
+    pub fn text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.data).ok()
+    }
+
+    /// JSON with the payload as both `text` (`null` unless UTF-8) and `hex`
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "text": self.text(),
+            "hex": hex::encode(&self.data),
+            "version": self.version,
+            "ec_level": self.ec_level,
+            "mask": self.mask,
+            "corners": self.corners,
+            "strategy": self.strategy,
+            "structured_append": self.structured_append,
+        })
+    }
+
+    fn center(&self) -> (i32, i32) {
+        let (x, y) = self
+            .corners
+            .iter()
+            .fold((0, 0), |(sx, sy), &(x, y)| (sx + x, sy + y));
+        (x / 4, y / 4)
+    }
+
+    fn width(&self) -> i32 {
+        (self.corners[1].0 - self.corners[0].0).abs().max(1)
+    }
+}
+
+/// A QR decoding backend used to cross-check readability
+pub trait Decoder: Send + Sync {
+    /// Short name used in logs and reports
+    fn name(&self) -> &'static str;
+
+    /// Payloads of every code found in the image
+    fn decode(&self, image: &GrayImage) -> Result<Vec<Vec<u8>>>;
+}
+
+/// The rqrr decoder. Validation always runs it first, since it also
+/// provides the version, mask and location in the report.
+pub struct RqrrDecoder;
+
+impl RqrrDecoder {
+    pub const NAME: &'static str = "rqrr";
+}
+
+impl Decoder for RqrrDecoder {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn decode(&self, image: &GrayImage) -> Result<Vec<Vec<u8>>> {
+        let detection = QrValidator::try_detect(image, &PreprocessStep::Identity)?;
+        Ok(detection.codes.into_iter().map(|(_, code)| code.data).collect())
+    }
+}
+
+/// A port of ZXing, whose tolerances are closer to those of phone scanners
+#[cfg(feature = "rxing")]
+pub struct RxingDecoder;
+
+#[cfg(feature = "rxing")]
+impl Decoder for RxingDecoder {
+    fn name(&self) -> &'static str {
+        "rxing"
+    }
+
+    fn decode(&self, image: &GrayImage) -> Result<Vec<Vec<u8>>> {
+        let mut hints = rxing::DecodeHints {
+            PossibleFormats: Some([rxing::BarcodeFormat::QR_CODE].into()),
+            // Latin-1 maps byte-mode data one-to-one onto chars
+            CharacterSet: Some("ISO-8859-1".to_string()),
+            ..Default::default()
+        };
+        let (width, height) = image.dimensions();
+        let results = rxing::helpers::detect_multiple_in_luma_with_hints(
+            image.as_raw().clone(),
+            width,
+            height,
+            &mut hints,
+        )
+        .map_err(|e| QrImageError::ValidationError(format!("rxing: {}", e)))?;
+
+        Ok(results
+            .iter()
+            .map(|result| {
+                let text = result.getText();
+                // ECI segments may produce characters outside Latin-1
+                if text.chars().all(|c| (c as u32) < 256) {
+                    text.chars().map(|c| c as u8).collect()
+                } else {
+                    text.as_bytes().to_vec()
+                }
+            })
+            .collect())
+    }
+}
+
+/// Decoder backends selectable from configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DecoderBackend {
+    Rqrr,
+    /// Requires the `rxing` cargo feature
+    Rxing,
+}
+
+impl DecoderBackend {
+    pub fn create(self) -> Result<Box<dyn Decoder>> {
+        match self {
+            DecoderBackend::Rqrr => Ok(Box::new(RqrrDecoder)),
+            #[cfg(feature = "rxing")]
+            DecoderBackend::Rxing => Ok(Box::new(RxingDecoder)),
+            #[cfg(not(feature = "rxing"))]
+            DecoderBackend::Rxing => Err(QrImageError::ConfigError(
+                "rxing decoder requires building with the `rxing` feature".to_string(),
+            )),
+        }
+    }
+}
+
+/// Whether one decoder read the expected payload
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecoderCheck {
+    pub decoder: String,
+    pub passed: bool,
+    /// Preprocessing strategy under which the decoder succeeded
+    pub strategy: Option<PreprocessStep>,
+    /// Why decoding failed, when it did
+    pub detail: Option<String>,
+}
+
+/// Every grid detected under one preprocessing strategy, with the codes that
+/// decoded, in source image coordinates
+struct Detection {
+    grid_corners: Vec<[(i32, i32); 4]>,
+    /// Decoded codes, each with the index of its grid
+    codes: Vec<(usize, DecodedCode)>,
+}
 
 pub struct QrValidator {
     max_attempts: u32,
+    pipeline: Vec<PreprocessStep>,
+    color_simulations: Vec<ColorSimulation>,
+    decoders: Vec<Box<dyn Decoder>>,
+    require_agreement: bool,
+    detect_strays: bool,
 }
 
 impl QrValidator {
     pub fn new(max_attempts: u32) -> Self {
-        Self { max_attempts }
+        Self {
+            max_attempts,
+            pipeline: PreprocessStep::default_pipeline(),
+            color_simulations: ColorSimulation::all(),
+            decoders: vec![Box::new(RqrrDecoder)],
+            require_agreement: false,
+            detect_strays: false,
+        }
+    }
+
+    /// Set the decoders cross-checked after rqrr validates the code. Checks
+    /// only run when a decoder other than rqrr is present.
+    pub fn with_decoders(mut self, decoders: Vec<Box<dyn Decoder>>) -> Self {
+        self.decoders = decoders;
+        self
+    }
+
+    /// Fail validation unless every decoder reads the expected payload;
+    /// otherwise disagreements are only reported
+    pub fn with_decoder_agreement(mut self, required: bool) -> Self {
+        self.require_agreement = required;
+        self
+    }
+
+    /// Run every strategy even after the expected code is found, so stray
+    /// codes that only a later strategy can read are rejected too
+    pub fn with_stray_detection(mut self, enabled: bool) -> Self {
+        self.detect_strays = enabled;
+        self
+    }
+
+    /// Set the colour simulations decoded after a successful validation
+    pub fn with_color_simulations(mut self, simulations: Vec<ColorSimulation>) -> Self {
+        self.color_simulations = simulations;
+        self
+    }
+
+    /// Set the preprocessing strategies tried in order, one per attempt.
+    /// Strategies beyond the attempt limit are never tried.
+    pub fn with_preprocessing(mut self, pipeline: Vec<PreprocessStep>) -> Self {
+        if pipeline.len() > self.max_attempts as usize {
+            info!(
+                "Only the first {} of {} preprocessing strategies will be tried (max_validation_attempts)",
+                self.max_attempts,
+                pipeline.len()
+            );
+        }
+        self.pipeline = pipeline;
+        self
+    }
+
+    /// Set the window size and the constant subtracted from the local mean
+    /// used by the adaptive threshold and Sauvola strategies
+    pub fn with_threshold(mut self, window: u32, offset: i32) -> Self {
+        self.pipeline = self
+            .pipeline
+            .into_iter()
+            .map(|step| step.with_threshold(window, offset))
+            .collect();
+        self
+    }
+
+    /// Strategies actually tried: the pipeline, capped at `max_attempts`
+    fn strategies(&self) -> impl Iterator<Item = &PreprocessStep> {
+        self.pipeline.iter().take(self.max_attempts as usize)
+    }
+
+    /// Why codes of this type cannot be validated, if they cannot
+    pub fn unsupported_reason(code_type: CodeType) -> Option<String> {
+        match code_type {
+            CodeType::Qr => None,
+            CodeType::MicroQr => Some(
+                "Micro QR decoding is not supported by the rqrr decoder".to_string(),
+            ),
+        }
+    }
+
+    /// Validate a code of the given type, skipping with a reason when no
+    /// decoder can read that type
+    pub fn validate_code(
+        &self,
+        image: &DynamicImage,
+        expected_data: &[u8],
+        code_type: CodeType,
+    ) -> Result<ValidationOutcome> {
+        if let Some(reason) = Self::unsupported_reason(code_type) {
+            warn!("Skipping QR code validation: {}", reason);
+            return Ok(ValidationOutcome::Skipped { reason });
+        }
+
+        let report = self.verify(image, expected_data)?;
+        Ok(ValidationOutcome::Verified(report))
     }
 
     /// Validate that QR code in image is readable and matches expected data
     pub fn validate(&self, image: &DynamicImage, expected_data: &str) -> Result<bool> {
+        self.validate_bytes(image, expected_data.as_bytes())
+    }
+
+    /// Validate a QR code built from explicit segments: the image must hold
+    /// exactly the segments' bytes, which decode under their ECI character
+    /// sets to the intended text
+    pub fn validate_segments(&self, image: &DynamicImage, expected: &[Segment]) -> Result<bool> {
+        self.verify_segments(image, expected).map(|_| true)
+    }
+
+    /// [`validate_code`](Self::validate_code) for a code built from explicit
+    /// segments, see [`validate_segments`](Self::validate_segments)
+    pub fn validate_segment_code(
+        &self,
+        image: &DynamicImage,
+        expected: &[Segment],
+        code_type: CodeType,
+    ) -> Result<ValidationOutcome> {
+        if let Some(reason) = Self::unsupported_reason(code_type) {
+            warn!("Skipping QR code validation: {}", reason);
+            return Ok(ValidationOutcome::Skipped { reason });
+        }
+
+        let report = self.verify_segments(image, expected)?;
+        Ok(ValidationOutcome::Verified(report))
+    }
+
+    fn verify_segments(&self, image: &DynamicImage, expected: &[Segment]) -> Result<ValidationReport> {
+        let expected_bytes = segments::decoded_bytes(expected)?;
+        let intended = segments::decode_text(expected, &expected_bytes)?;
+
+        let report = self.verify(image, &expected_bytes).map_err(|e| match e {
+            QrImageError::ValidationError(_) => self
+                .segment_mismatch(image, expected, &expected_bytes, &intended)
+                .unwrap_or(e),
+            e => e,
+        })?;
+        debug!("Decoded segments as text: {}", intended);
+        Ok(report)
+    }
+
+    /// Describe the text of a code that holds other bytes than the segments
+    fn segment_mismatch(
+        &self,
+        image: &DynamicImage,
+        expected: &[Segment],
+        expected_bytes: &[u8],
+        intended: &str,
+    ) -> Option<QrImageError> {
+        let code = self
+            .decode_all(image)
+            .into_iter()
+            .find(|code| code.data != expected_bytes)?;
+        let message = match segments::decode_text(expected, &code.data) {
+            Ok(text) => format!("QR code reads '{}' instead of '{}'", text, intended),
+            Err(e) => format!("QR code text does not decode: {}", e),
+        };
+        Some(QrImageError::ValidationError(message))
+    }
+
+    /// Validate that QR code in image decodes to exactly the expected bytes
+    pub fn validate_bytes(&self, image: &DynamicImage, expected_data: &[u8]) -> Result<bool> {
+        self.verify(image, expected_data).map(|_| true)
+    }
+
+    /// Run every preprocessing strategy, returning a report of the first
+    /// one that decoded the expected code
+    ///
+    /// Every code decoded by any strategy must carry the expected data; any
+    /// other code, such as one printed in the background photo, fails
+    /// validation with [`QrImageError::UnexpectedQrCode`].
+    pub fn verify(&self, image: &DynamicImage, expected_data: &[u8]) -> Result<ValidationReport> {
         info!("Starting QR code validation");
 
-        for attempt in 1..=self.max_attempts {
-            debug!("Validation attempt {}/{}", attempt, self.max_attempts);
-
-            match self.try_decode(image, attempt) {
-                Ok(decoded_data) => {
-                    info!("QR code decoded successfully");
-                    debug!("Decoded data length: {}", decoded_data.len());
-
-                    if decoded_data == expected_data {
-                        info!("QR code validation successful - data matches");
-                        return Ok(true);
-                    } else {
-                        warn!("QR code decoded but data mismatch");
-                        debug!("Expected: {}", expected_data);
-                        debug!("Got: {}", decoded_data);
-                        return Err(QrImageError::ValidationError(
-                            "Decoded data does not match expected data".to_string(),
-                        ));
+        let source = image.to_luma8();
+        let started = Instant::now();
+        let attempts = self.strategies().count();
+        let mut strays: Vec<DecodedCode> = Vec::new();
+        let mut first_match = None;
+        for (attempt, strategy) in self.strategies().enumerate() {
+            debug!(
+                "Validation attempt {}/{}: {}",
+                attempt + 1,
+                attempts,
+                strategy
+            );
+
+            let detection = match Self::try_detect(&source, strategy) {
+                Ok(detection) => detection,
+                Err(e) => {
+                    warn!("Attempt {} ({}) failed: {}", attempt + 1, strategy, e);
+                    continue;
+                }
+            };
+            info!(
+                "{} QR code(s) decoded using {}",
+                detection.codes.len(),
+                strategy
+            );
+
+            for (_, code) in &detection.codes {
+                if code.data != expected_data && !strays.iter().any(|s| s.data == code.data) {
+                    warn!("Unexpected QR code at {:?}", code.corners);
+                    debug!("Expected: {}", String::from_utf8_lossy(expected_data));
+                    debug!("Got: {}", String::from_utf8_lossy(&code.data));
+                    strays.push(code.clone());
+                }
+            }
+
+            if first_match.is_none() {
+                if let Some(found) = detection
+                    .codes
+                    .iter()
+                    .position(|(_, code)| code.data == expected_data)
+                {
+                    let decode_time_ms = started.elapsed().as_secs_f64() * 1000.0;
+                    first_match = Some((detection, found, attempt + 1, decode_time_ms));
+                }
+            }
+            if first_match.is_some() && !self.detect_strays {
+                break;
+            }
+        }
+
+        let Some((detection, found, attempt, decode_time_ms)) = first_match else {
+            if !strays.is_empty() {
+                return Err(QrImageError::ValidationError(
+                    "Decoded data does not match expected data".to_string(),
+                ));
+            }
+            return Err(QrImageError::ValidationError(format!(
+                "Failed to decode QR code after {} attempts",
+                attempts
+            )));
+        };
+        if !strays.is_empty() {
+            return Err(QrImageError::UnexpectedQrCode {
+                count: strays.len(),
+            });
+        }
+
+        info!("QR code validation successful - data matches");
+        let mut report = Self::build_report(&source, detection, found, attempt, decode_time_ms);
+        report.decoder_checks = self.decoder_checks(&source, expected_data);
+        let failed: Vec<&str> = report
+            .decoder_checks
+            .iter()
+            .filter(|check| !check.passed)
+            .map(|check| check.decoder.as_str())
+            .collect();
+        if !failed.is_empty() {
+            warn!("Decoders disagree: {} could not read the code", failed.join(", "));
+            if self.require_agreement {
+                return Err(QrImageError::ValidationError(format!(
+                    "Decoders disagree: {} could not read the code",
+                    failed.join(", ")
+                )));
+            }
+        }
+        report.color_checks = self.color_checks(image, expected_data);
+        Ok(report)
+    }
+
+    fn build_report(
+        source: &GrayImage,
+        mut detection: Detection,
+        found: usize,
+        attempt: usize,
+        decode_time_ms: f64,
+    ) -> ValidationReport {
+        let (grid, code) = detection.codes.swap_remove(found);
+        let contrast = measure_contrast(source, &detection.grid_corners[grid]);
+
+        ValidationReport {
+            version: code.version,
+            ec_level: code.ec_level,
+            mask: code.mask,
+            grids_found: detection.grid_corners.len(),
+            grid_corners: detection.grid_corners,
+            decoded_grid: grid,
+            strategy: code.strategy,
+            attempt,
+            decode_time_ms,
+            contrast,
+            color_checks: Vec::new(),
+            decoder_checks: Vec::new(),
+        }
+    }
+
+    /// Run every configured decoder through the preprocessing strategies
+    /// until it reads the expected payload. Skipped when rqrr, which has
+    /// already read the code, is the only decoder.
+    fn decoder_checks(&self, source: &GrayImage, expected_data: &[u8]) -> Vec<DecoderCheck> {
+        if self
+            .decoders
+            .iter()
+            .all(|decoder| decoder.name() == RqrrDecoder::NAME)
+        {
+            return Vec::new();
+        }
+
+        self.decoders
+            .iter()
+            .map(|decoder| {
+                let mut detail = None;
+                for strategy in self.strategies() {
+                    match decoder.decode(&strategy.apply(source)) {
+                        Ok(payloads) if payloads.iter().any(|p| p == expected_data) => {
+                            debug!("{} read the code using {}", decoder.name(), strategy);
+                            return DecoderCheck {
+                                decoder: decoder.name().to_string(),
+                                passed: true,
+                                strategy: Some(strategy.clone()),
+                                detail: None,
+                            };
+                        }
+                        Ok(_) => {
+                            detail = Some("Decoded data does not match expected data".to_string())
+                        }
+                        Err(e) => detail = Some(e.to_string()),
                     }
                 }
-                Err(e) => {
-                    warn!("Attempt {} failed: {}", attempt, e);
-                    if attempt == self.max_attempts {
-                        return Err(QrImageError::ValidationError(format!(
-                            "Failed to decode QR code after {} attempts",
-                            self.max_attempts
-                        )));
+
+                DecoderCheck {
+                    decoder: decoder.name().to_string(),
+                    passed: false,
+                    strategy: None,
+                    detail,
+                }
+            })
+            .collect()
+    }
+
+    /// Decode the image once under each colour simulation, as a camera would
+    /// see a reprinted or differently perceived copy
+    fn color_checks(&self, image: &DynamicImage, expected_data: &[u8]) -> Vec<ColorCheck> {
+        self.color_simulations
+            .iter()
+            .map(|&simulation| {
+                let simulated = simulation.apply(image).to_luma8();
+                let detail = match Self::try_detect(&simulated, &PreprocessStep::Identity) {
+                    Ok(detection)
+                        if detection
+                            .codes
+                            .iter()
+                            .any(|(_, code)| code.data == expected_data) =>
+                    {
+                        None
                     }
+                    Ok(_) => Some("Decoded data does not match expected data".to_string()),
+                    Err(e) => Some(e.to_string()),
+                };
+                match &detail {
+                    Some(reason) => warn!("Code unreadable under {}: {}", simulation, reason),
+                    None => debug!("{}: passed", simulation),
                 }
+
+                ColorCheck {
+                    simulation,
+                    passed: detail.is_none(),
+                    detail,
+                }
+            })
+            .collect()
+    }
+
+    /// Find and decode every QR code in an image, running each preprocessing
+    /// strategy and merging what they find
+    ///
+    /// Codes rqrr cannot parse, such as Structured Append symbols, are read
+    /// with the built-in symbol decoder.
+    pub fn decode_all(&self, image: &DynamicImage) -> Vec<DecodedCode> {
+        let source = image.to_luma8();
+        let mut codes: Vec<DecodedCode> = Vec::new();
+
+        for strategy in self.strategies() {
+            let Ok(detection) = Self::try_detect(&source, strategy) else {
+                continue;
+            };
+
+            for (_, code) in detection.codes {
+                // The same code found again under another strategy
+                let (cx, cy) = code.center();
+                let duplicate = codes.iter().any(|known| {
+                    let (kx, ky) = known.center();
+                    known.data == code.data
+                        && (kx - cx).abs() < known.width() / 2
+                        && (ky - cy).abs() < known.width() / 2
+                });
+                if !duplicate {
+                    debug!("Found QR code with {} bytes using {}", code.data.len(), strategy);
+                    codes.push(code);
+                }
+            }
+        }
+
+        info!("Found {} QR code(s)", codes.len());
+        codes
+    }
+
+    /// Measure module-level contrast of the first decodable code in the image,
+    /// locating it with the preprocessing strategies but sampling the
+    /// original pixels
+    pub fn legibility(&self, image: &DynamicImage) -> Result<LegibilityReport> {
+        use rqrr::BitGrid;
+
+        let source = image.to_luma8();
+        for strategy in self.strategies() {
+            let gray_image = strategy.apply(&source);
+            let found = std::panic::catch_unwind(move || {
+                let mut prepared = PreparedImage::prepare(gray_image);
+                prepared.detect_grids().iter().find_map(|grid| {
+                    let size = grid.grid.size();
+                    symbol_decoder::ideal_modules(size, |x, y| grid.grid.bit(y, x))
+                        .ok()
+                        .map(|ideal| (size, ideal, grid.bounds.map(|p| (p.x, p.y))))
+                })
+            })
+            .unwrap_or_default();
+
+            if let Some((size, ideal, bounds)) = found {
+                debug!("Analysing legibility of code found using {}", strategy);
+                let corners = module_area(bounds, size)
+                    .map(|point| strategy.source_point(&source, point));
+                return legibility::analyze(image, corners, size, ideal);
             }
         }
 
         Err(QrImageError::ValidationError(
-            "QR code validation failed".to_string(),
+            "No decodable QR code to analyse".to_string(),
         ))
     }
 
-    fn try_decode(&self, image: &DynamicImage, attempt: u32) -> Result<String> {
-        // Convert to grayscale for better QR detection
-        let gray_image = self.preprocess_image(image, attempt)?;
+    /// Re-test the image under the standard degradation matrix and score how
+    /// many degradations the code survives
+    pub fn robustness_report(&self, image: &DynamicImage, expected_data: &[u8]) -> RobustnessReport {
+        self.robustness_report_with(image, expected_data, &Degradation::standard_matrix())
+    }
+
+    /// Re-test the image under the given degradations. Each degraded image
+    /// gets a single decode pass, as a phone camera would.
+    pub fn robustness_report_with(
+        &self,
+        image: &DynamicImage,
+        expected_data: &[u8],
+        degradations: &[Degradation],
+    ) -> RobustnessReport {
+        info!("Scoring robustness under {} degradation(s)", degradations.len());
+
+        let results = degradations
+            .iter()
+            .map(|&degradation| {
+                let outcome = degradation
+                    .apply(image)
+                    .and_then(|degraded| {
+                        Self::try_detect(&degraded.to_luma8(), &PreprocessStep::Identity)
+                    })
+                    .map(|detection| {
+                        detection
+                            .codes
+                            .iter()
+                            .any(|(_, code)| code.data == expected_data)
+                    });
+                let detail = match outcome {
+                    Ok(true) => None,
+                    Ok(false) => Some("Decoded data does not match expected data".to_string()),
+                    Err(e) => Some(e.to_string()),
+                };
+                debug!(
+                    "{}: {}",
+                    degradation,
+                    detail.as_deref().unwrap_or("passed")
+                );
+
+                DegradationResult {
+                    degradation,
+                    passed: detail.is_none(),
+                    detail,
+                }
+            })
+            .collect();
+
+        let report = RobustnessReport::from_results(results);
+        info!("Robustness score: {}/100", report.score);
+        report
+    }
+
+    /// Validate that every symbol of a Structured Append sequence decodes and
+    /// that the pieces reassemble to the expected data
+    pub fn validate_structured(&self, image: &DynamicImage, expected_data: &[u8]) -> Result<bool> {
+        info!("Starting Structured Append validation");
+
+        let mut symbols: Vec<DecodedSymbol> = Vec::new();
+        let attempts = self.strategies().count();
+        for (attempt, strategy) in self.strategies().enumerate() {
+            debug!("Validation attempt {}/{}: {}", attempt + 1, attempts, strategy);
+
+            // Pieces found by different preprocessing strategies add up
+            let gray_image = strategy.apply(&image.to_luma8());
+            let found = std::panic::catch_unwind(move || Self::decode_symbols(gray_image))
+                .unwrap_or_default();
+            for symbol in found {
+                add_symbol(&mut symbols, symbol);
+            }
+
+            match structured_append::reassemble(&symbols) {
+                Ok(data) if data == expected_data => {
+                    info!(
+                        "Structured Append validation successful - {} symbol(s) reassembled",
+                        symbols.len()
+                    );
+                    return Ok(true);
+                }
+                Ok(_) => {
+                    return Err(QrImageError::ValidationError(
+                        "Reassembled data does not match expected data".to_string(),
+                    ));
+                }
+                Err(e) => warn!("Attempt {} ({}) incomplete: {}", attempt + 1, strategy, e),
+            }
+        }
+
+        Err(QrImageError::ValidationError(format!(
+            "Structured Append sequence incomplete after {} attempts ({} symbol(s) decoded)",
+            attempts,
+            symbols.len()
+        )))
+    }
+
+    /// Decode every Structured Append symbol in the image. rqrr groups finder
+    /// patterns poorly when several codes are aligned, so decoded symbols are
+    /// erased and detection repeated, then overlapping windows are scanned
+    /// until the sequence is complete.
+    fn decode_symbols(mut gray_image: GrayImage) -> Vec<DecodedSymbol> {
+        let mut symbols = Vec::new();
+
+        for _ in 0..structured_append::MAX_SYMBOLS {
+            let found = Self::decode_grids(gray_image.clone());
+            if found.is_empty() {
+                break;
+            }
+            for (symbol, bounds) in found {
+                erase_region(&mut gray_image, &bounds);
+                add_symbol(&mut symbols, symbol);
+            }
+        }
+
+        let (width, height) = gray_image.dimensions();
+        for divisions in 2..=4u32 {
+            if is_complete(&symbols) {
+                break;
+            }
+            let (window_w, window_h) = (width / divisions, height / divisions);
+            for y in (0..=height - window_h).step_by((window_h / 2).max(1) as usize) {
+                for x in (0..=width - window_w).step_by((window_w / 2).max(1) as usize) {
+                    let window =
+                        image::imageops::crop_imm(&gray_image, x, y, window_w, window_h).to_image();
+                    for (symbol, _) in Self::decode_grids(window) {
+                        add_symbol(&mut symbols, symbol);
+                    }
+                }
+            }
+        }
+
+        symbols
+    }
+
+    fn decode_grids(gray_image: GrayImage) -> Vec<(DecodedSymbol, [rqrr::Point; 4])> {
+        use rqrr::BitGrid;
+
+        let mut prepared = PreparedImage::prepare(gray_image);
+        prepared
+            .detect_grids()
+            .iter()
+            .filter_map(|grid| {
+                let size = grid.grid.size();
+                symbol_decoder::decode_modules(size, |x, y| grid.grid.bit(y, x))
+                    .map(|symbol| (symbol, grid.bounds))
+                    .map_err(|e| debug!("Symbol decode failed: {}", e))
+                    .ok()
+            })
+            .collect()
+    }
+
+    fn try_detect(source: &GrayImage, strategy: &PreprocessStep) -> Result<Detection> {
+        let gray_image = strategy.apply(source);
+
+        // rqrr asserts on some degenerate finder geometry; treat a panic in
+        // detection as a failed attempt rather than aborting validation
+        let mut detection = std::panic::catch_unwind(move || Self::detect(gray_image))
+            .unwrap_or_else(|_| {
+                Err(QrImageError::ValidationError(
+                    "QR decoder failed on this image".to_string(),
+                ))
+            })?;
+
+        // Grid coordinates are in the preprocessed image; report them in the
+        // source image so they match the file on disk
+        for corners in &mut detection.grid_corners {
+            *corners = corners.map(|point| strategy.source_point(source, point));
+        }
+        for (grid, code) in &mut detection.codes {
+            code.corners = detection.grid_corners[*grid];
+            code.strategy = strategy.clone();
+        }
+        Ok(detection)
+    }
+
+    fn detect(gray_image: GrayImage) -> Result<Detection> {
+        use rqrr::BitGrid;
 
         // Prepare image for QR detection
         let mut prepared = PreparedImage::prepare(gray_image);
@@ -70,138 +854,163 @@ impl QrValidator {
         }
 
         debug!("Detected {} QR code grid(s)", grids.len());
+        let grid_corners: Vec<[(i32, i32); 4]> = grids
+            .iter()
+            .map(|grid| grid.bounds.map(|p| (p.x, p.y)))
+            .collect();
 
-        // Try to decode each detected grid
+        // Decode every grid, not just the first, so stray codes are seen
+        let mut codes = Vec::new();
         for (i, grid) in grids.iter().enumerate() {
             debug!("Attempting to decode grid {}", i + 1);
 
-            match grid.decode() {
-                Ok((meta, content)) => {
+            // Decode to raw bytes: the payload need not be UTF-8 (ECI, Kanji)
+            let mut content = Vec::new();
+            let code = match grid.decode_to(&mut content) {
+                Ok(meta) => {
                     debug!("QR code decoded: version={:?}", meta.version);
-                    // content is already a String in rqrr 0.7
-                    return Ok(content);
+                    DecodedCode {
+                        data: content,
+                        version: meta.version.0 as u8,
+                        ec_level: ec_level_from_format(meta.ecc_level),
+                        mask: meta.mask as u8,
+                        corners: grid_corners[i],
+                        strategy: PreprocessStep::Identity,
+                        structured_append: None,
+                    }
                 }
                 Err(e) => {
+                    // rqrr rejects Structured Append symbols; the built-in
+                    // symbol decoder reads them
                     debug!("Grid {} decode failed: {:?}", i + 1, e);
+                    let size = grid.grid.size();
+                    match symbol_decoder::decode_modules(size, |x, y| grid.grid.bit(y, x)) {
+                        Ok(symbol) => DecodedCode {
+                            data: symbol.data,
+                            version: symbol.version as u8,
+                            ec_level: symbol.ec_level.into(),
+                            mask: symbol.mask,
+                            corners: grid_corners[i],
+                            strategy: PreprocessStep::Identity,
+                            structured_append: symbol.structured_append,
+                        },
+                        Err(_) => continue,
+                    }
                 }
-            }
+            };
+            codes.push((i, code));
         }
 
-        Err(QrImageError::ValidationError(
-            "QR codes detected but none could be decoded".to_string(),
-        ))
-    }
-
-    fn preprocess_image(&self, image: &DynamicImage, attempt: u32) -> Result<GrayImage> {
-        let mut gray = image.to_luma8();
-
-        // Apply different preprocessing strategies based on attempt
-        match attempt {
-            1 => {
-                // First attempt: use original image
-                debug!("Using original image");
-            }
-            2 => {
-                // Second attempt: increase contrast
-                debug!("Applying contrast enhancement");
-                self.enhance_contrast(&mut gray);
-            }
-            3 => {
-                // Third attempt: apply adaptive threshold
-                debug!("Applying adaptive thresholding");
-                self.adaptive_threshold(&mut gray);
-            }
-            _ => {
-                // Additional attempts: try brightness adjustment
-                debug!("Applying brightness adjustment");
-                self.adjust_brightness(&mut gray, attempt as i32);
-            }
+        if codes.is_empty() {
+            return Err(QrImageError::ValidationError(
+                "QR codes detected but none could be decoded".to_string(),
+            ));
         }
+        Ok(Detection {
+            grid_corners,
+            codes,
+        })
+    }
 
-        Ok(gray)
+    /// Quick check that the image holds at least one decodable QR code,
+    /// without preprocessing
+    pub fn quick_check(&self, image: &DynamicImage) -> bool {
+        Self::try_detect(&image.to_luma8(), &PreprocessStep::Identity).is_ok()
     }
+}
 
-    fn enhance_contrast(&self, image: &mut GrayImage) {
-        // Simple contrast stretching
-        let (width, height) = image.dimensions();
-        let mut min_val = 255u8;
-        let mut max_val = 0u8;
-
-        // Find min and max
-        for y in 0..height {
-            for x in 0..width {
-                let pixel = image.get_pixel(x, y)[0];
-                min_val = min_val.min(pixel);
-                max_val = max_val.max(pixel);
-            }
-        }
+/// Outer corners of the module area. rqrr's grid bounds span `size + 1`
+/// modules, so they overshoot the bottom-right edge by one module.
+fn module_area(bounds: [(i32, i32); 4], size: usize) -> [(i32, i32); 4] {
+    let unit = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+    let Some(projection) = imageproc::geometric_transformations::Projection::from_control_points(
+        unit,
+        bounds.map(|(x, y)| (x as f32, y as f32)),
+    ) else {
+        return bounds;
+    };
 
-        if max_val > min_val {
-            let range = max_val - min_val;
-            for y in 0..height {
-                for x in 0..width {
-                    let pixel = image.get_pixel(x, y)[0];
-                    let normalized = ((pixel - min_val) as f32 / range as f32 * 255.0) as u8;
-                    image.put_pixel(x, y, image::Luma([normalized]));
-                }
-            }
-        }
-    }
+    let edge = size as f32 / (size + 1) as f32;
+    unit.map(|(u, v)| {
+        let (x, y) = projection * (u * edge, v * edge);
+        (x.round() as i32, y.round() as i32)
+    })
+}
 
-    fn adaptive_threshold(&self, image: &mut GrayImage) {
-        let (width, height) = image.dimensions();
-        let mut result = image.clone();
-
-        // Simple adaptive thresholding
-        let window_size = 15u32;
-        let c = 10i32; // Constant subtracted from mean
-
-        for y in 0..height {
-            for x in 0..width {
-                let mut sum = 0u32;
-                let mut count = 0u32;
-
-                // Calculate local mean
-                for dy in 0..window_size {
-                    for dx in 0..window_size {
-                        let px = (x + dx).saturating_sub(window_size / 2);
-                        let py = (y + dy).saturating_sub(window_size / 2);
-
-                        if px < width && py < height {
-                            sum += image.get_pixel(px, py)[0] as u32;
-                            count += 1;
-                        }
-                    }
-                }
+/// Error correction level from the two format-information bits
+fn ec_level_from_format(bits: u16) -> ErrorCorrection {
+    match bits & 0b11 {
+        0b01 => ErrorCorrection::Low,
+        0b00 => ErrorCorrection::Medium,
+        0b11 => ErrorCorrection::Quartile,
+        _ => ErrorCorrection::High,
+    }
+}
 
-                let mean = (sum / count.max(1)) as i32;
-                let pixel = image.get_pixel(x, y)[0] as i32;
-                let threshold = mean - c;
+/// Separate the pixels inside the code's bounding box into dark and light
+/// with Otsu's threshold and return the difference of their means (0-1)
+fn measure_contrast(image: &GrayImage, corners: &[(i32, i32); 4]) -> f32 {
+    let clamp_x = |x: i32| x.clamp(0, image.width() as i32) as u32;
+    let clamp_y = |y: i32| y.clamp(0, image.height() as i32) as u32;
+    let x0 = clamp_x(corners.iter().map(|c| c.0).min().unwrap_or(0));
+    let x1 = clamp_x(corners.iter().map(|c| c.0).max().unwrap_or(0));
+    let y0 = clamp_y(corners.iter().map(|c| c.1).min().unwrap_or(0));
+    let y1 = clamp_y(corners.iter().map(|c| c.1).max().unwrap_or(0));
+    if x1 <= x0 || y1 <= y0 {
+        return 0.0;
+    }
 
-                let new_val = if pixel > threshold { 255 } else { 0 };
-                result.put_pixel(x, y, image::Luma([new_val]));
-            }
+    let region = image::imageops::crop_imm(image, x0, y0, x1 - x0, y1 - y0).to_image();
+    let level = binarize::otsu_level(&region);
+    let (mut dark, mut dark_count, mut light, mut light_count) = (0u64, 0u64, 0u64, 0u64);
+    for pixel in region.pixels() {
+        if pixel[0] > level {
+            light += pixel[0] as u64;
+            light_count += 1;
+        } else {
+            dark += pixel[0] as u64;
+            dark_count += 1;
         }
-
-        *image = result;
+    }
+    if dark_count == 0 || light_count == 0 {
+        return 0.0;
     }
 
-    fn adjust_brightness(&self, image: &mut GrayImage, factor: i32) {
-        let adjustment = (factor - 3) * 20; // -40, -20, 0, 20, 40, ...
+    (light as f32 / light_count as f32 - dark as f32 / dark_count as f32) / 255.0
+}
 
-        for pixel in image.pixels_mut() {
-            let val = pixel[0] as i32 + adjustment;
-            pixel[0] = val.clamp(0, 255) as u8;
-        }
+/// Keep one symbol per Structured Append index, ignoring plain QR codes
+fn add_symbol(symbols: &mut Vec<DecodedSymbol>, symbol: DecodedSymbol) {
+    let Some(header) = symbol.structured_append else {
+        return;
+    };
+    if !symbols
+        .iter()
+        .any(|s| s.structured_append.map(|h| h.index) == Some(header.index))
+    {
+        symbols.push(symbol);
     }
+}
 
-    /// Quick check if image likely contains a readable QR code
-    pub fn quick_check(&self, image: &DynamicImage) -> bool {
-        let gray = image.to_luma8();
-        let mut prepared = PreparedImage::prepare(gray);
-        let grids = prepared.detect_grids();
+/// Whether one symbol of every index in the sequence has been found
+fn is_complete(symbols: &[DecodedSymbol]) -> bool {
+    symbols
+        .first()
+        .and_then(|s| s.structured_append)
+        .is_some_and(|header| symbols.len() >= header.total as usize)
+}
+
+/// Paint the bounding box of a detected symbol white
+fn erase_region(image: &mut GrayImage, bounds: &[rqrr::Point; 4]) {
+    let xs = bounds.iter().map(|p| p.x.clamp(0, image.width() as i32) as u32);
+    let ys = bounds.iter().map(|p| p.y.clamp(0, image.height() as i32) as u32);
+    let (x0, x1) = (xs.clone().min().unwrap_or(0), xs.max().unwrap_or(0));
+    let (y0, y1) = (ys.clone().min().unwrap_or(0), ys.max().unwrap_or(0));
 
-        !grids.is_empty()
+    for y in y0..y1.min(image.height()) {
+        for x in x0..x1.min(image.width()) {
+            image.put_pixel(x, y, Luma([255]));
+        }
     }
 }
 
@@ -224,4 +1033,305 @@ mod tests {
         assert!(result.is_ok());
         assert!(result.unwrap());
     }
+
+    #[test]
+    fn test_validate_binary_payload() {
+        // Invalid UTF-8 on purpose: a String comparison could never match this
+        let data: Vec<u8> = vec![0x00, 0xff, 0xfe, 0x80, 0x7f, 0xc3, 0x28, 0x01];
+        let code = QrCode::new(&data).unwrap();
+        let image = code.render::<image::Luma<u8>>().build();
+        let dynamic = DynamicImage::ImageLuma8(image);
+
+        let validator = QrValidator::new(3);
+        assert!(validator.validate_bytes(&dynamic, &data).unwrap());
+        assert!(validator.validate_bytes(&dynamic, &data[1..]).is_err());
+    }
+
+    #[test]
+    fn test_micro_qr_validation_skipped() {
+        let code = QrCode::with_version(b"01234", qrcode::Version::Micro(1), qrcode::EcLevel::L)
+            .unwrap();
+        let image = code.render::<image::Luma<u8>>().build();
+        let dynamic = DynamicImage::ImageLuma8(image);
+
+        let validator = QrValidator::new(3);
+        let outcome = validator
+            .validate_code(&dynamic, b"01234", CodeType::MicroQr)
+            .unwrap();
+        assert!(matches!(outcome, ValidationOutcome::Skipped { .. }));
+    }
+
+    #[test]
+    fn test_reports_successful_strategy() {
+        let embedder = crate::qr_embedder::QrEmbedder::new(crate::config::Config::default());
+        let mut image = embedder
+            .embed_qr_code(DynamicImage::new_rgb8(400, 400), "light on dark")
+            .unwrap();
+        image.invert();
+
+        let identity_only = QrValidator::new(3).with_preprocessing(vec![PreprocessStep::Identity]);
+        assert!(identity_only.verify(&image, b"light on dark").is_err());
+
+        let validator = QrValidator::new(3)
+            .with_preprocessing(vec![PreprocessStep::Identity, PreprocessStep::Invert]);
+        let outcome = validator
+            .validate_code(&image, b"light on dark", CodeType::Qr)
+            .unwrap();
+        match outcome {
+            ValidationOutcome::Verified(report) => assert_eq!(report.strategy, PreprocessStep::Invert),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_stray_code_fails_validation() {
+        use crate::config::{Config, QrPosition};
+
+        let background = DynamicImage::new_rgb8(1200, 800);
+        let stray = crate::qr_embedder::QrEmbedder::new(
+            Config::default().with_qr_position(QrPosition::TopLeft),
+        )
+        .embed_qr_code(background, "https://example.com/poster")
+        .unwrap();
+        let image = crate::qr_embedder::QrEmbedder::new(Config::default())
+            .embed_qr_code(stray, "https://example.com/ours")
+            .unwrap();
+
+        let validator = QrValidator::new(5);
+        let mut payloads: Vec<Vec<u8>> = validator
+            .decode_all(&image)
+            .into_iter()
+            .map(|code| code.data)
+            .collect();
+        payloads.sort();
+        assert_eq!(
+            payloads,
+            vec![
+                b"https://example.com/ours".to_vec(),
+                b"https://example.com/poster".to_vec()
+            ]
+        );
+
+        assert!(matches!(
+            validator.verify(&image, b"https://example.com/ours"),
+            Err(QrImageError::UnexpectedQrCode { count: 1 })
+        ));
+        assert!(validator.quick_check(&image));
+    }
+
+    #[test]
+    fn test_stray_code_found_by_later_strategy() {
+        use crate::config::{Config, QrPosition};
+
+        let mut stray = crate::qr_embedder::QrEmbedder::new(
+            Config::default().with_qr_position(QrPosition::TopLeft),
+        )
+        .embed_qr_code(DynamicImage::new_rgb8(1200, 800), "https://example.com/poster")
+        .unwrap();
+        stray.invert();
+        let image = crate::qr_embedder::QrEmbedder::new(Config::default())
+            .embed_qr_code(stray, "https://example.com/ours")
+            .unwrap();
+
+        // Identity reads our code; only Invert reads the light-on-dark stray
+        let validator = QrValidator::new(3)
+            .with_preprocessing(vec![PreprocessStep::Identity, PreprocessStep::Invert]);
+        assert!(validator.verify(&image, b"https://example.com/ours").is_ok());
+        let validator = validator.with_stray_detection(true);
+        assert!(matches!(
+            validator.verify(&image, b"https://example.com/ours"),
+            Err(QrImageError::UnexpectedQrCode { count: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_legibility_tracks_opacity() {
+        use crate::config::Config;
+
+        // A mid-grey background bleeds through a translucent tile
+        let background = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            800,
+            600,
+            image::Rgb([128, 128, 128]),
+        ));
+        let analyse = |opacity: u8| {
+            let config = Config {
+                qr_background_opacity: opacity,
+                ..Config::default()
+            };
+            let image = crate::qr_embedder::QrEmbedder::new(config)
+                .embed_qr_code(background.clone(), "https://example.com/legible")
+                .unwrap();
+            QrValidator::new(5).legibility(&image).unwrap()
+        };
+
+        let opaque = analyse(255);
+        assert_eq!(opaque.wrong_percent, 0.0);
+        assert_eq!(opaque.weak_percent, 0.0);
+        assert!(opaque.contrast_ratio > 15.0, "{}", opaque.contrast_ratio);
+        assert_eq!(opaque.margins.len(), opaque.size * opaque.size);
+
+        let translucent = analyse(150);
+        assert!(translucent.contrast_ratio < opaque.contrast_ratio);
+        assert!(translucent.light.mean < opaque.light.mean);
+    }
+
+    /// A decoder that never finds anything
+    struct BlindDecoder;
+
+    impl Decoder for BlindDecoder {
+        fn name(&self) -> &'static str {
+            "blind"
+        }
+
+        fn decode(&self, _image: &GrayImage) -> Result<Vec<Vec<u8>>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn test_decoder_agreement() {
+        let embedder = crate::qr_embedder::QrEmbedder::new(crate::config::Config::default());
+        let image = embedder
+            .embed_qr_code(DynamicImage::new_rgb8(400, 400), "cross-checked")
+            .unwrap();
+        let validator = || {
+            QrValidator::new(2)
+                .with_color_simulations(Vec::new())
+                .with_decoders(vec![Box::new(RqrrDecoder), Box::new(BlindDecoder)])
+        };
+
+        let report = validator().verify(&image, b"cross-checked").unwrap();
+        assert_eq!(report.decoder_checks.len(), 2);
+        assert!(report.decoder_checks[0].passed);
+        assert_eq!(report.decoder_checks[0].strategy, Some(PreprocessStep::Identity));
+        assert!(!report.decoder_checks[1].passed);
+
+        let strict = validator().with_decoder_agreement(true);
+        assert!(strict.verify(&image, b"cross-checked").is_err());
+    }
+
+    #[cfg(feature = "rxing")]
+    #[test]
+    fn test_rxing_reads_binary_payload() {
+        let data = [0x00, 0xff, 0x80, b'q', b'r'];
+        let embedder = crate::qr_embedder::QrEmbedder::new(crate::config::Config::default());
+        let image = embedder
+            .embed_qr_bytes(DynamicImage::new_rgb8(400, 400), &data)
+            .unwrap();
+
+        let report = QrValidator::new(5)
+            .with_decoders(vec![Box::new(RqrrDecoder), Box::new(RxingDecoder)])
+            .with_decoder_agreement(true)
+            .verify(&image, &data)
+            .unwrap();
+        assert!(report.decoder_checks.iter().all(|check| check.passed));
+    }
+
+    #[test]
+    fn test_validation_report_metadata() {
+        let config = crate::config::Config::default()
+            .with_error_correction(ErrorCorrection::Quartile)
+            .with_qr_position(crate::config::QrPosition::Center);
+        let embedder = crate::qr_embedder::QrEmbedder::new(config);
+        let image = embedder
+            .embed_qr_code(DynamicImage::new_rgb8(800, 600), "https://example.com/report")
+            .unwrap();
+
+        let report = QrValidator::new(3)
+            .verify(&image, b"https://example.com/report")
+            .unwrap();
+        assert_eq!(report.version, 3);
+        assert_eq!(report.ec_level, ErrorCorrection::Quartile);
+        assert!(report.mask < 8);
+        assert_eq!(report.grids_found, 1);
+        assert_eq!(report.attempt, 1);
+        assert!(report.contrast > 0.8);
+        assert_eq!(report.color_checks.len(), ColorSimulation::all().len());
+        assert!(report.color_checks.iter().all(|check| check.passed));
+
+        // The code sits in the middle of the image
+        let (x, y) = report.grid_corners[0][0];
+        assert!((250..400).contains(&x) && (150..300).contains(&y));
+
+        let json = report.to_json().unwrap();
+        let parsed: ValidationReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.version, report.version);
+        assert_eq!(parsed.ec_level, report.ec_level);
+    }
+
+    #[test]
+    fn test_decode_all_finds_structured_append_symbols() {
+        let data: Vec<u8> = (0..400u32).map(|i| (i % 89) as u8 + b' ').collect();
+        let symbols = structured_append::split(&data, qrcode::EcLevel::M, 6).unwrap();
+        let config = crate::config::Config::default().with_dimensions(1600, 1600);
+        let embedder = crate::qr_embedder::QrEmbedder::new(config);
+        let image = embedder
+            .embed_structured(DynamicImage::new_rgb8(1600, 1600), &symbols[..1])
+            .unwrap();
+
+        let codes = QrValidator::new(1).decode_all(&image);
+        assert_eq!(codes.len(), 1);
+        assert_eq!(codes[0].data, symbols[0].data);
+        assert_eq!(codes[0].structured_append.unwrap().total, symbols.len() as u8);
+        assert!(codes[0].text().is_some());
+    }
+
+    #[test]
+    fn test_robustness_report() {
+        let config = crate::config::Config::default().with_dimensions(400, 400);
+        let embedder = crate::qr_embedder::QrEmbedder::new(config);
+        let image = embedder
+            .embed_qr_code(DynamicImage::new_rgb8(400, 400), "https://example.com")
+            .unwrap();
+
+        let validator = QrValidator::new(3);
+        let degradations = [
+            Degradation::JpegQuality(75),
+            Degradation::Rotation(10.0),
+            Degradation::Downscale(0.05),
+        ];
+        let report = validator.robustness_report_with(&image, b"https://example.com", &degradations);
+
+        assert_eq!(report.results.len(), 3);
+        assert!(report.results[0].passed && report.results[1].passed);
+        assert!(!report.results[2].passed);
+        assert_eq!(report.score, 66);
+    }
+
+    #[test]
+    fn test_validate_structured_append() {
+        let data: Vec<u8> = (0..400u32).map(|i| (i % 97) as u8 + b' ').collect();
+        let symbols = structured_append::split(&data, qrcode::EcLevel::M, 6).unwrap();
+        assert!(symbols.len() > 1);
+
+        let config = crate::config::Config::default().with_dimensions(1600, 1600);
+        let embedder = crate::qr_embedder::QrEmbedder::new(config);
+        let background = DynamicImage::new_rgb8(1600, 1600);
+        let image = embedder.embed_structured(background, &symbols).unwrap();
+
+        let validator = QrValidator::new(3);
+        assert!(validator.validate_structured(&image, &data).unwrap());
+        assert!(validator.validate_structured(&image, &data[1..]).is_err());
+    }
+
+    #[test]
+    fn test_validate_segments_round_trip() {
+        let mut expected = vec![Segment::Numeric("2024".to_string())];
+        expected.extend(Segment::text(segments::Charset::ShiftJis, "日本語テキスト").unwrap());
+        expected.extend(Segment::text(segments::Charset::EucKr, "한국어").unwrap());
+
+        let code = segments::encode_segments(&expected, CodeType::Qr, qrcode::EcLevel::M).unwrap();
+        let image = code.render::<image::Luma<u8>>().build();
+        let dynamic = DynamicImage::ImageLuma8(image);
+
+        let validator = QrValidator::new(3);
+        assert!(validator.validate_segments(&dynamic, &expected).unwrap());
+
+        let mut other = vec![Segment::Numeric("2024".to_string())];
+        other.extend(Segment::text(segments::Charset::ShiftJis, "日本語テキスト").unwrap());
+        other.extend(Segment::text(segments::Charset::EucKr, "한국인").unwrap());
+        let error = validator.validate_segments(&dynamic, &other).unwrap_err();
+        assert!(error.to_string().contains("한국어"), "{}", error);
+    }
 }
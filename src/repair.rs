@@ -0,0 +1,167 @@
+use crate::config::{Config, ErrorCorrection, QrPosition};
+use crate::image_provider::BackgroundInfo;
+use crate::qr_validator::ValidationReport;
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Remedy kinds in the order they are escalated
+const ESCALATION: [RemedyKind; 5] = [
+    RemedyKind::Opacity,
+    RemedyKind::Size,
+    RemedyKind::ErrorCorrection,
+    RemedyKind::Position,
+    RemedyKind::Background,
+];
+
+/// Positions tried in turn when moving the code
+const POSITIONS: [QrPosition; 5] = [
+    QrPosition::BottomRight,
+    QrPosition::BottomLeft,
+    QrPosition::TopRight,
+    QrPosition::TopLeft,
+    QrPosition::Center,
+];
+
+#[derive(Debug, Clone, Copy)]
+enum RemedyKind {
+    Opacity,
+    Size,
+    ErrorCorrection,
+    Position,
+    Background,
+}
+
+/// A change made to get a generated code past validation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Adjustment {
+    /// Background opacity behind the code raised to this value
+    Opacity(u8),
+    /// Code size ratio increased to this value
+    SizeRatio(f32),
+    /// Error correction raised to this level
+    ErrorCorrection(ErrorCorrection),
+    /// Code moved to this position
+    Position(QrPosition),
+    /// A different background image was fetched
+    NewBackground,
+}
+
+impl Adjustment {
+    /// Record the adjustment in the configuration used for the next attempt
+    pub fn apply(&self, config: &mut Config) {
+        match self {
+            Adjustment::Opacity(opacity) => config.qr_background_opacity = *opacity,
+            Adjustment::SizeRatio(ratio) => config.qr_size_ratio = *ratio,
+            Adjustment::ErrorCorrection(level) => config.error_correction = *level,
+            Adjustment::Position(position) => config.qr_position = position.clone(),
+            Adjustment::NewBackground => {}
+        }
+    }
+}
+
+impl fmt::Display for Adjustment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Adjustment::Opacity(opacity) => write!(f, "raised background opacity to {}", opacity),
+            Adjustment::SizeRatio(ratio) => write!(f, "increased QR size ratio to {:.2}", ratio),
+            Adjustment::ErrorCorrection(level) => {
+                write!(f, "raised error correction to {:?}", level)
+            }
+            Adjustment::Position(position) => write!(f, "moved QR code to {:?}", position),
+            Adjustment::NewBackground => write!(f, "fetched a new background image"),
+        }
+    }
+}
+
+/// An image that passed validation, with the adjustments needed to get there
+#[derive(Debug, Clone)]
+pub struct RepairedImage {
+    pub image: DynamicImage,
+    /// The background the code was drawn on
+    pub background_image: DynamicImage,
+    /// Adjustments in the order they were made; empty if the first attempt passed
+    pub adjustments: Vec<Adjustment>,
+    /// What the decoder read back; `None` when validation was skipped or the
+    /// data was split with Structured Append
+    pub report: Option<ValidationReport>,
+    /// Where the background behind the code came from
+    pub background: BackgroundInfo,
+}
+
+/// The remedy for repair step `step` (0-based), given the configuration after
+/// the previous steps. Remedies rotate through opacity, size, error
+/// correction, position and background, skipping any already at its limit.
+pub fn next_adjustment(config: &Config, step: usize) -> Option<Adjustment> {
+    (0..ESCALATION.len())
+        .map(|offset| ESCALATION[(step + offset) % ESCALATION.len()])
+        .find_map(|kind| remedy(config, kind))
+}
+
+fn remedy(config: &Config, kind: RemedyKind) -> Option<Adjustment> {
+    match kind {
+        RemedyKind::Opacity => (config.qr_background_opacity < 255).then(|| {
+            Adjustment::Opacity(config.qr_background_opacity.saturating_add(25))
+        }),
+        RemedyKind::Size => (config.qr_size_ratio < 0.5)
+            .then(|| Adjustment::SizeRatio((config.qr_size_ratio + 0.05).min(0.5))),
+        RemedyKind::ErrorCorrection => {
+            let next = match config.error_correction {
+                ErrorCorrection::Low => Some(ErrorCorrection::Medium),
+                ErrorCorrection::Medium => Some(ErrorCorrection::Quartile),
+                ErrorCorrection::Quartile => Some(ErrorCorrection::High),
+                ErrorCorrection::High => None,
+            };
+            next.map(Adjustment::ErrorCorrection)
+        }
+        RemedyKind::Position => {
+            let current = POSITIONS
+                .iter()
+                .position(|p| *p == config.qr_position)
+                .unwrap_or(0);
+            Some(Adjustment::Position(
+                POSITIONS[(current + 1) % POSITIONS.len()].clone(),
+            ))
+        }
+        RemedyKind::Background => Some(Adjustment::NewBackground),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escalation_order() {
+        let mut config = Config::default();
+        let mut adjustments = Vec::new();
+        for step in 0..5 {
+            let adjustment = next_adjustment(&config, step).unwrap();
+            adjustment.apply(&mut config);
+            adjustments.push(adjustment);
+        }
+
+        assert_eq!(
+            adjustments,
+            vec![
+                Adjustment::Opacity(255),
+                Adjustment::SizeRatio(0.3),
+                Adjustment::ErrorCorrection(ErrorCorrection::Quartile),
+                Adjustment::Position(QrPosition::BottomLeft),
+                Adjustment::NewBackground,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exhausted_remedies_are_skipped() {
+        let mut config = Config::default().with_error_correction(ErrorCorrection::High);
+        config.qr_background_opacity = 255;
+        config.qr_size_ratio = 0.5;
+
+        assert_eq!(
+            next_adjustment(&config, 0),
+            Some(Adjustment::Position(QrPosition::BottomLeft))
+        );
+    }
+}
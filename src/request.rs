@@ -0,0 +1,253 @@
+use crate::config::{CodeType, Config, QrPosition};
+use crate::error::{QrImageError, Result};
+use crate::image_provider::{Background, BackgroundInfo, BackgroundSource};
+use crate::metadata::ImageMetadata;
+use crate::output::{self, OutputFormat};
+use crate::qr_embedder::QrEmbedder;
+use crate::qr_validator::ValidationReport;
+use crate::repair::Adjustment;
+use crate::structured_append;
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// What to put the code on
+#[derive(Debug, Clone)]
+pub enum BackgroundSpec {
+    /// Photo found for a keyword
+    Keyword(String),
+    /// The caller's own image; the output takes its dimensions
+    Image(DynamicImage),
+}
+
+/// One code to generate, with settings for this call only. Anything not set
+/// falls back to the generator's config.
+#[derive(Debug, Clone)]
+pub struct GenerationRequest {
+    pub data: Vec<u8>,
+    pub background: BackgroundSpec,
+    pub dark_color: Option<[u8; 3]>,
+    pub light_color: Option<[u8; 3]>,
+    pub opacity: Option<u8>,
+    pub position: Option<QrPosition>,
+    pub size_ratio: Option<f32>,
+    pub format: OutputFormat,
+    /// Config fields by name, plus `preset`; applied before the style and
+    /// placement settings above
+    pub overrides: serde_json::Map<String, serde_json::Value>,
+}
+
+impl GenerationRequest {
+    /// Encode `data` on a photo found for `keyword`
+    pub fn new(keyword: &str, data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            data: data.into(),
+            background: BackgroundSpec::Keyword(keyword.to_string()),
+            dark_color: None,
+            light_color: None,
+            opacity: None,
+            position: None,
+            size_ratio: None,
+            format: OutputFormat::default(),
+            overrides: serde_json::Map::new(),
+        }
+    }
+
+    /// Search for a photo with this keyword
+    pub fn with_keyword(mut self, keyword: &str) -> Self {
+        self.background = BackgroundSpec::Keyword(keyword.to_string());
+        self
+    }
+
+    /// Put the code on this image instead of a downloaded photo
+    pub fn with_background(mut self, image: DynamicImage) -> Self {
+        self.background = BackgroundSpec::Image(image);
+        self
+    }
+
+    pub fn with_colors(mut self, dark: [u8; 3], light: [u8; 3]) -> Self {
+        self.dark_color = Some(dark);
+        self.light_color = Some(light);
+        self
+    }
+
+    pub fn with_opacity(mut self, opacity: u8) -> Self {
+        self.opacity = Some(opacity);
+        self
+    }
+
+    pub fn with_position(mut self, position: QrPosition) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    pub fn with_size_ratio(mut self, ratio: f32) -> Self {
+        self.size_ratio = Some(ratio);
+        self
+    }
+
+    pub fn with_format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Apply a built-in or user-defined preset
+    pub fn with_preset(mut self, name: &str) -> Self {
+        self.overrides.insert("preset".to_string(), name.into());
+        self
+    }
+
+    /// Override one config field by name, e.g. `("error_correction", "High")`
+    pub fn with_override(mut self, field: &str, value: impl Into<serde_json::Value>) -> Self {
+        self.overrides.insert(field.to_string(), value.into());
+        self
+    }
+
+    /// Override several config fields by name
+    pub fn with_overrides(mut self, overrides: serde_json::Map<String, serde_json::Value>) -> Self {
+        self.overrides.extend(overrides);
+        self
+    }
+
+    /// The config this request runs with: `base`, then the overrides, then
+    /// style and placement, then the supplied background's dimensions
+    pub fn resolve_config(&self, base: &Config) -> Result<Config> {
+        let mut config = base.clone().with_preset_and_overrides(self.overrides.clone())?;
+        if let Some(dark) = self.dark_color {
+            config.dark_color = dark;
+        }
+        if let Some(light) = self.light_color {
+            config.light_color = light;
+        }
+        if let Some(opacity) = self.opacity {
+            config.qr_background_opacity = opacity;
+        }
+        if let Some(position) = &self.position {
+            config.qr_position = position.clone();
+        }
+        if let Some(ratio) = self.size_ratio {
+            config.qr_size_ratio = ratio;
+        }
+        if let BackgroundSpec::Image(image) = &self.background {
+            config.image_width = image.width();
+            config.image_height = image.height();
+        }
+        Ok(config)
+    }
+}
+
+/// Where the code ended up
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Placement {
+    /// Position after any repairs
+    pub position: QrPosition,
+    /// Size ratio after any repairs
+    pub size_ratio: f32,
+    /// Bounding box (x, y, width, height) in image pixels of the code as the
+    /// validator found it, when validation ran
+    pub bounds: Option<(u32, u32, u32, u32)>,
+}
+
+/// A generated image and how it was made
+#[derive(Debug, Clone)]
+pub struct GenerationResult {
+    pub image: DynamicImage,
+    /// The background before the code was drawn on it
+    pub background_image: DynamicImage,
+    pub placement: Placement,
+    pub background: BackgroundInfo,
+    pub report: Option<ValidationReport>,
+    /// Repairs made to get the code past validation
+    pub adjustments: Vec<Adjustment>,
+    pub format: OutputFormat,
+    /// The config the image was finally generated with, repairs included
+    pub config: Config,
+    /// Payload, generator and photo credit for embedding in saved files
+    pub metadata: ImageMetadata,
+}
+
+impl GenerationResult {
+    /// The image in the requested format. SVG redraws the code as vector
+    /// shapes over the background rather than wrapping the raster.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        if self.format != OutputFormat::Svg {
+            return output::encode_with_metadata(
+                &self.image,
+                self.format,
+                self.config.dpi,
+                self.embedded_metadata(),
+            );
+        }
+
+        let payload = &self.metadata.payload;
+        if self.config.code_type == CodeType::Qr
+            && structured_append::needs_split(payload, self.config.error_correction.into())
+        {
+            return Err(QrImageError::EncodingError(
+                "SVG output does not support Structured Append".to_string(),
+            ));
+        }
+        let layers = QrEmbedder::new(self.config.clone()).svg_tile(&self.background_image, payload)?;
+        output::encode_svg(&self.background_image, &layers, self.embedded_metadata())
+    }
+
+    /// Metadata to write into saved files, unless `embed_metadata` is off
+    pub fn embedded_metadata(&self) -> Option<&ImageMetadata> {
+        self.config.embed_metadata.then_some(&self.metadata)
+    }
+
+    /// Write the encoded image to `path`
+    pub fn save(&self, path: &Path) -> Result<()> {
+        Ok(std::fs::write(path, self.encode()?)?)
+    }
+}
+
+/// Bounding box of a validated code's corners
+pub(crate) fn code_bounds(report: &ValidationReport) -> Option<(u32, u32, u32, u32)> {
+    let corners = report.grid_corners.get(report.decoded_grid)?;
+    let xs = corners.iter().map(|&(x, _)| x.max(0) as u32);
+    let ys = corners.iter().map(|&(_, y)| y.max(0) as u32);
+    let (left, right) = (xs.clone().min()?, xs.max()?);
+    let (top, bottom) = (ys.clone().min()?, ys.max()?);
+    Some((left, top, right - left, bottom - top))
+}
+
+/// A caller-supplied background, which cannot be swapped for another
+pub(crate) struct SuppliedBackground<'a>(pub &'a DynamicImage);
+
+impl BackgroundSource for SuppliedBackground<'_> {
+    fn background(&self, _keyword: &str) -> Result<Background> {
+        Ok((self.0.clone(), BackgroundInfo::supplied(self.0)))
+    }
+
+    fn new_background(&self, _keyword: &str, _draw: u32) -> Result<Background> {
+        Err(QrImageError::ProviderError(
+            "a supplied background cannot be replaced".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_config_layers() {
+        let base = Config::default().with_qr_position(QrPosition::TopLeft);
+        let request = GenerationRequest::new("sea", "x")
+            .with_preset("instagram-post")
+            .with_override("error_correction", "High")
+            .with_position(QrPosition::Center)
+            .with_colors([10, 20, 30], [240, 240, 240]);
+
+        let config = request.resolve_config(&base).unwrap();
+        assert_eq!((config.image_width, config.image_height), (1080, 1080));
+        assert_eq!(config.qr_position, QrPosition::Center);
+        assert_eq!(config.dark_color, [10, 20, 30]);
+        assert_eq!(config.error_correction, crate::config::ErrorCorrection::High);
+
+        let supplied = request.with_background(DynamicImage::new_rgb8(640, 480));
+        let config = supplied.resolve_config(&base).unwrap();
+        assert_eq!((config.image_width, config.image_height), (640, 480));
+    }
+}
@@ -0,0 +1,222 @@
+use crate::error::Result;
+use crate::rng::SplitMix64;
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, GrayImage, Luma};
+use imageproc::geometric_transformations::{self, Interpolation, Projection};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Seed for simulated sensor noise, fixed so scores are reproducible
+const NOISE_SEED: u64 = 0x5eed_c0de;
+
+/// A simulated real-world degradation applied before re-decoding
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Degradation {
+    /// Shrink to this fraction of the original size
+    Downscale(f32),
+    /// Gaussian blur with this sigma in pixels
+    GaussianBlur(f32),
+    /// Re-encode as JPEG at this quality (1-100)
+    JpegQuality(u8),
+    /// Additive Gaussian noise with this standard deviation (0-255 scale)
+    Noise(f32),
+    /// Rotate clockwise by this many degrees
+    Rotation(f32),
+    /// Perspective skew: the top edge shrinks by this fraction of the width
+    PerspectiveSkew(f32),
+    /// Shift every pixel's brightness by this amount (-255 to 255)
+    Brightness(i32),
+}
+
+impl Degradation {
+    /// Degradations checked by default, from mild to severe in each group
+    pub fn standard_matrix() -> Vec<Degradation> {
+        vec![
+            Degradation::Downscale(0.5),
+            Degradation::Downscale(0.35),
+            Degradation::Downscale(0.25),
+            Degradation::GaussianBlur(1.0),
+            Degradation::GaussianBlur(2.0),
+            Degradation::GaussianBlur(3.0),
+            Degradation::JpegQuality(75),
+            Degradation::JpegQuality(50),
+            Degradation::JpegQuality(20),
+            Degradation::Noise(10.0),
+            Degradation::Noise(25.0),
+            Degradation::Rotation(5.0),
+            Degradation::Rotation(15.0),
+            Degradation::Rotation(45.0),
+            Degradation::PerspectiveSkew(0.1),
+            Degradation::PerspectiveSkew(0.2),
+            Degradation::Brightness(60),
+            Degradation::Brightness(-60),
+        ]
+    }
+
+    /// Apply the degradation, returning a grayscale image as a scanner sees it
+    pub fn apply(&self, image: &DynamicImage) -> Result<DynamicImage> {
+        let degraded = match *self {
+            Degradation::Downscale(factor) => {
+                let width = ((image.width() as f32 * factor) as u32).max(1);
+                let height = ((image.height() as f32 * factor) as u32).max(1);
+                image
+                    .resize_exact(width, height, image::imageops::FilterType::Triangle)
+                    .to_luma8()
+            }
+            Degradation::GaussianBlur(sigma) => {
+                imageproc::filter::gaussian_blur_f32(&image.to_luma8(), sigma)
+            }
+            Degradation::JpegQuality(quality) => {
+                // Encode in colour so chroma subsampling artefacts are included
+                let mut buffer = Vec::new();
+                JpegEncoder::new_with_quality(&mut buffer, quality)
+                    .encode_image(&DynamicImage::ImageRgb8(image.to_rgb8()))?;
+                image::load_from_memory(&buffer)?.to_luma8()
+            }
+            Degradation::Noise(sigma) => {
+                let mut rng = SplitMix64::new(NOISE_SEED);
+                let mut gray = image.to_luma8();
+                for pixel in gray.pixels_mut() {
+                    let value = pixel[0] as f32 + rng.next_gaussian() * sigma;
+                    pixel[0] = value.clamp(0.0, 255.0) as u8;
+                }
+                gray
+            }
+            Degradation::Rotation(degrees) => rotate(&image.to_luma8(), degrees),
+            Degradation::PerspectiveSkew(amount) => skew(&image.to_luma8(), amount),
+            Degradation::Brightness(delta) => image::imageops::brighten(&image.to_luma8(), delta),
+        };
+
+        Ok(DynamicImage::ImageLuma8(degraded))
+    }
+}
+
+impl fmt::Display for Degradation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Degradation::Downscale(factor) => write!(f, "downscale to {:.0}%", factor * 100.0),
+            Degradation::GaussianBlur(sigma) => write!(f, "gaussian blur σ={}", sigma),
+            Degradation::JpegQuality(quality) => write!(f, "JPEG quality {}", quality),
+            Degradation::Noise(sigma) => write!(f, "noise σ={}", sigma),
+            Degradation::Rotation(degrees) => write!(f, "rotation {}°", degrees),
+            Degradation::PerspectiveSkew(amount) => {
+                write!(f, "perspective skew {:.0}%", amount * 100.0)
+            }
+            Degradation::Brightness(delta) => write!(f, "brightness {:+}", delta),
+        }
+    }
+}
+
+/// Result of re-decoding after one degradation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DegradationResult {
+    pub degradation: Degradation,
+    pub passed: bool,
+    /// Why decoding failed, when it did
+    pub detail: Option<String>,
+}
+
+/// How well a code survives the degradation matrix
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RobustnessReport {
+    /// Percentage of degradations the code survived (0-100)
+    pub score: u8,
+    pub results: Vec<DegradationResult>,
+}
+
+impl RobustnessReport {
+    pub fn from_results(results: Vec<DegradationResult>) -> Self {
+        let passed = results.iter().filter(|r| r.passed).count();
+        let score = if results.is_empty() {
+            100
+        } else {
+            (passed * 100 / results.len()) as u8
+        };
+
+        Self { score, results }
+    }
+
+    /// Degradations the code did not survive
+    pub fn failures(&self) -> impl Iterator<Item = &DegradationResult> {
+        self.results.iter().filter(|r| !r.passed)
+    }
+}
+
+/// Rotate on an enlarged canvas so corner-placed codes are not clipped
+fn rotate(image: &GrayImage, degrees: f32) -> GrayImage {
+    let theta = degrees.to_radians();
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    let new_width = (width * theta.cos().abs() + height * theta.sin().abs()).round() as u32;
+    let new_height = (width * theta.sin().abs() + height * theta.cos().abs()).round() as u32;
+    let (new_width, new_height) = (new_width.max(image.width()), new_height.max(image.height()));
+
+    let mut canvas = GrayImage::from_pixel(new_width, new_height, Luma([255]));
+    image::imageops::overlay(
+        &mut canvas,
+        image,
+        ((new_width - image.width()) / 2) as i64,
+        ((new_height - image.height()) / 2) as i64,
+    );
+
+    geometric_transformations::rotate_about_center(
+        &canvas,
+        theta,
+        Interpolation::Bilinear,
+        Luma([255]),
+    )
+}
+
+/// Map the image onto a trapezoid, as if photographed from below
+fn skew(image: &GrayImage, amount: f32) -> GrayImage {
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    let inset = width * amount / 2.0;
+    let from = [(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)];
+    let to = [
+        (inset, 0.0),
+        (width - inset, 0.0),
+        (width, height),
+        (0.0, height),
+    ];
+
+    match Projection::from_control_points(from, to) {
+        Some(projection) => geometric_transformations::warp(
+            image,
+            &projection,
+            Interpolation::Bilinear,
+            Luma([255]),
+        ),
+        None => image.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_degradations_apply() {
+        let image = DynamicImage::new_rgb8(120, 80);
+        for degradation in Degradation::standard_matrix() {
+            let degraded = degradation.apply(&image).unwrap();
+            match degradation {
+                Degradation::Downscale(_) | Degradation::Rotation(_) => {}
+                _ => assert_eq!(degraded.width(), 120, "{}", degradation),
+            }
+        }
+
+        let rotated = Degradation::Rotation(45.0).apply(&image).unwrap();
+        assert!(rotated.width() > 120 && rotated.height() > 80);
+    }
+
+    #[test]
+    fn test_report_score() {
+        let result = |passed| DegradationResult {
+            degradation: Degradation::Noise(10.0),
+            passed,
+            detail: None,
+        };
+        let report = RobustnessReport::from_results(vec![result(true), result(true), result(false)]);
+        assert_eq!(report.score, 66);
+        assert_eq!(report.failures().count(), 1);
+    }
+}
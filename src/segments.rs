@@ -0,0 +1,330 @@
+use crate::config::CodeType;
+use crate::error::{QrImageError, Result};
+use encoding_rs::Encoding;
+use log::debug;
+use qrcode::bits::Bits;
+use qrcode::types::QrError;
+use qrcode::{EcLevel, QrCode};
+use serde::{Deserialize, Serialize};
+
+/// Character sets that can be announced with an ECI designator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Charset {
+    Utf8,
+    /// ISO-8859-n, where n is the part number (1-16, except 12)
+    Iso8859(u8),
+    ShiftJis,
+    EucKr,
+}
+
+impl Charset {
+    /// ECI assignment number for this character set
+    pub fn eci_designator(self) -> Result<u32> {
+        match self {
+            Charset::Utf8 => Ok(26),
+            Charset::ShiftJis => Ok(20),
+            Charset::EucKr => Ok(30),
+            Charset::Iso8859(part @ (1..=11 | 13..=16)) => Ok(part as u32 + 2),
+            Charset::Iso8859(part) => Err(QrImageError::EncodingError(format!(
+                "ISO-8859-{} has no ECI assignment",
+                part
+            ))),
+        }
+    }
+
+    /// Encode text into this character set, failing on unmappable characters
+    pub fn encode(self, text: &str) -> Result<Vec<u8>> {
+        match self {
+            Charset::Utf8 => Ok(text.as_bytes().to_vec()),
+            Charset::Iso8859(1) => text
+                .chars()
+                .map(|c| {
+                    u8::try_from(c as u32).map_err(|_| {
+                        QrImageError::EncodingError(format!(
+                            "Character {:?} is not representable in ISO-8859-1",
+                            c
+                        ))
+                    })
+                })
+                .collect(),
+            _ => {
+                let encoding = self.encoding()?;
+                let (bytes, _, had_errors) = encoding.encode(text);
+                if had_errors {
+                    return Err(QrImageError::EncodingError(format!(
+                        "Text is not representable in {}",
+                        encoding.name()
+                    )));
+                }
+                Ok(bytes.into_owned())
+            }
+        }
+    }
+
+    /// Decode bytes from this character set, failing on malformed input
+    pub fn decode(self, bytes: &[u8]) -> Result<String> {
+        match self {
+            Charset::Utf8 => String::from_utf8(bytes.to_vec())
+                .map_err(|e| QrImageError::EncodingError(e.to_string())),
+            Charset::Iso8859(1) => Ok(bytes.iter().map(|&b| b as char).collect()),
+            _ => {
+                let encoding = self.encoding()?;
+                encoding
+                    .decode_without_bom_handling_and_without_replacement(bytes)
+                    .map(|text| text.into_owned())
+                    .ok_or_else(|| {
+                        QrImageError::EncodingError(format!(
+                            "Bytes are not valid {}",
+                            encoding.name()
+                        ))
+                    })
+            }
+        }
+    }
+
+    fn encoding(self) -> Result<&'static Encoding> {
+        let encoding = match self {
+            Charset::Utf8 => Some(encoding_rs::UTF_8),
+            Charset::ShiftJis => Some(encoding_rs::SHIFT_JIS),
+            Charset::EucKr => Some(encoding_rs::EUC_KR),
+            Charset::Iso8859(part) => Encoding::for_label(format!("iso-8859-{}", part).as_bytes()),
+        };
+
+        encoding.ok_or_else(|| {
+            QrImageError::EncodingError(format!("Unsupported character set: {:?}", self))
+        })
+    }
+}
+
+/// A single QR data segment with an explicitly chosen encoding mode
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Segment {
+    /// Digits 0-9
+    Numeric(String),
+    /// Digits, upper-case letters, space and `$%*+-./:`
+    Alphanumeric(String),
+    /// Arbitrary bytes, interpreted according to the active ECI
+    Byte(Vec<u8>),
+    /// Text restricted to the Shift JIS double-byte Kanji range
+    Kanji(String),
+    /// ECI designator switching the interpretation of following byte segments
+    Eci(Charset),
+}
+
+impl Segment {
+    /// Text in the given character set, preceded by its ECI designator
+    pub fn text(charset: Charset, text: &str) -> Result<Vec<Segment>> {
+        Ok(vec![
+            Segment::Eci(charset),
+            Segment::Byte(charset.encode(text)?),
+        ])
+    }
+
+    /// Bytes a standard decoder emits for this segment (ECI designators emit nothing)
+    pub fn decoded_bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            Segment::Numeric(digits) => Ok(digits.as_bytes().to_vec()),
+            Segment::Alphanumeric(text) => Ok(text.as_bytes().to_vec()),
+            Segment::Byte(bytes) => Ok(bytes.clone()),
+            Segment::Kanji(text) => kanji_bytes(text),
+            Segment::Eci(_) => Ok(Vec::new()),
+        }
+    }
+
+    fn push_to(&self, bits: &mut Bits) -> Result<()> {
+        let pushed = match self {
+            Segment::Numeric(digits) => {
+                if !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(QrImageError::EncodingError(
+                        "Numeric segment may only contain digits 0-9".to_string(),
+                    ));
+                }
+                bits.push_numeric_data(digits.as_bytes())
+            }
+            Segment::Alphanumeric(text) => {
+                if !text.bytes().all(is_alphanumeric) {
+                    return Err(QrImageError::EncodingError(
+                        "Alphanumeric segment contains characters outside 0-9, A-Z, space and $%*+-./:"
+                            .to_string(),
+                    ));
+                }
+                bits.push_alphanumeric_data(text.as_bytes())
+            }
+            Segment::Byte(bytes) => bits.push_byte_data(bytes),
+            Segment::Kanji(text) => bits.push_kanji_data(&kanji_bytes(text)?),
+            Segment::Eci(charset) => bits.push_eci_designator(charset.eci_designator()?),
+        };
+
+        pushed.map_err(QrImageError::from)
+    }
+}
+
+/// Concatenated bytes a standard decoder is expected to return for these segments
+pub fn decoded_bytes(segments: &[Segment]) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for segment in segments {
+        bytes.extend(segment.decoded_bytes()?);
+    }
+    Ok(bytes)
+}
+
+/// Interpret decoded bytes as text, applying the ECI designators of the segments
+/// they were encoded from. Segments without a preceding ECI are read as UTF-8.
+pub fn decode_text(segments: &[Segment], decoded: &[u8]) -> Result<String> {
+    let mut text = String::new();
+    let mut charset = Charset::Utf8;
+    let mut offset = 0;
+
+    for segment in segments {
+        if let Segment::Eci(next) = segment {
+            charset = *next;
+            continue;
+        }
+
+        let len = segment.decoded_bytes()?.len();
+        let chunk = decoded.get(offset..offset + len).ok_or_else(|| {
+            QrImageError::EncodingError("Decoded data is shorter than the segments".to_string())
+        })?;
+        text.push_str(&match segment {
+            Segment::Kanji(_) => Charset::ShiftJis.decode(chunk)?,
+            Segment::Byte(_) => charset.decode(chunk)?,
+            _ => String::from_utf8_lossy(chunk).into_owned(),
+        });
+        offset += len;
+    }
+
+    Ok(text)
+}
+
+/// Build a code from explicit segments, using the smallest version of the
+/// code type that fits. Micro QR does not support ECI designators.
+pub fn encode_segments(
+    segments: &[Segment],
+    code_type: CodeType,
+    ec_level: EcLevel,
+) -> Result<QrCode> {
+    if segments.is_empty() {
+        return Err(QrImageError::EncodingError(
+            "At least one segment is required".to_string(),
+        ));
+    }
+
+    // Smaller Micro QR versions lack some modes and EC levels, so those
+    // errors only mean "try the next size"; report them if nothing fits.
+    let mut last_error = QrError::DataTooLong;
+
+    'versions: for version in code_type.versions() {
+        let mut bits = Bits::new(version);
+
+        for segment in segments {
+            match segment.push_to(&mut bits) {
+                Ok(()) => {}
+                Err(QrImageError::QrCodeError(
+                    e @ (QrError::DataTooLong | QrError::UnsupportedCharacterSet),
+                )) => {
+                    last_error = e;
+                    continue 'versions;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        match bits.push_terminator(ec_level) {
+            Ok(()) => {
+                debug!("Segments fit in version {:?}", version);
+                return Ok(QrCode::with_bits(bits, ec_level)?);
+            }
+            Err(e @ (QrError::DataTooLong | QrError::InvalidVersion)) => last_error = e,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Err(last_error.into())
+}
+
+fn is_alphanumeric(b: u8) -> bool {
+    matches!(b, b'0'..=b'9' | b'A'..=b'Z' | b' ' | b'$' | b'%' | b'*' | b'+' | b'-' | b'.' | b'/' | b':')
+}
+
+fn kanji_bytes(text: &str) -> Result<Vec<u8>> {
+    let bytes = Charset::ShiftJis.encode(text)?;
+
+    let in_range = bytes.len() % 2 == 0
+        && bytes.chunks(2).all(|pair| {
+            let code = u16::from_be_bytes([pair[0], pair[1]]);
+            (0x8140..=0x9ffc).contains(&code) || (0xe040..=0xebbf).contains(&code)
+        });
+
+    if !in_range {
+        return Err(QrImageError::EncodingError(
+            "Kanji segment may only contain Shift JIS double-byte characters".to_string(),
+        ));
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eci_designators() {
+        assert_eq!(Charset::Utf8.eci_designator().unwrap(), 26);
+        assert_eq!(Charset::ShiftJis.eci_designator().unwrap(), 20);
+        assert_eq!(Charset::Iso8859(1).eci_designator().unwrap(), 3);
+        assert_eq!(Charset::Iso8859(15).eci_designator().unwrap(), 17);
+        assert!(Charset::Iso8859(12).eci_designator().is_err());
+    }
+
+    #[test]
+    fn test_charset_round_trip() {
+        let korean = Charset::EucKr.encode("안녕하세요").unwrap();
+        assert_eq!(Charset::EucKr.decode(&korean).unwrap(), "안녕하세요");
+
+        let latin = Charset::Iso8859(1).encode("café").unwrap();
+        assert_eq!(latin, b"caf\xe9");
+        assert!(Charset::Iso8859(1).encode("日本").is_err());
+    }
+
+    #[test]
+    fn test_encode_mixed_segments() {
+        let mut segments = vec![
+            Segment::Numeric("0123456789".to_string()),
+            Segment::Alphanumeric("HELLO WORLD".to_string()),
+            Segment::Kanji("漢字".to_string()),
+        ];
+        segments.extend(Segment::text(Charset::ShiftJis, "こんにちは").unwrap());
+
+        let code = encode_segments(&segments, CodeType::Qr, EcLevel::M).unwrap();
+        assert_eq!(code.error_correction_level(), EcLevel::M);
+
+        let decoded = decoded_bytes(&segments).unwrap();
+        assert_eq!(
+            decode_text(&segments, &decoded).unwrap(),
+            "0123456789HELLO WORLD漢字こんにちは"
+        );
+    }
+
+    #[test]
+    fn test_encode_micro_segments() {
+        let segments = [Segment::Numeric("01234567".to_string())];
+        let code = encode_segments(&segments, CodeType::MicroQr, EcLevel::L).unwrap();
+        assert_eq!(code.version(), qrcode::Version::Micro(2));
+
+        let eci = Segment::text(Charset::Utf8, "hi").unwrap();
+        assert!(encode_segments(&eci, CodeType::MicroQr, EcLevel::L).is_err());
+    }
+
+    #[test]
+    fn test_invalid_segments_rejected() {
+        let numeric = [Segment::Numeric("12a".to_string())];
+        assert!(encode_segments(&numeric, CodeType::Qr, EcLevel::L).is_err());
+
+        let alphanumeric = [Segment::Alphanumeric("lower".to_string())];
+        assert!(encode_segments(&alphanumeric, CodeType::Qr, EcLevel::L).is_err());
+
+        let kanji = [Segment::Kanji("abc".to_string())];
+        assert!(encode_segments(&kanji, CodeType::Qr, EcLevel::L).is_err());
+    }
+}
@@ -0,0 +1,518 @@
+use crate::config::Config;
+use crate::error::{QrImageError, Result};
+use crate::metadata::{self, ImageMetadata};
+use crate::output::OutputFormat;
+use crate::qr_validator::{DecodedCode, QrValidator};
+use crate::request::GenerationRequest;
+use crate::QrImageGenerator;
+use base64::Engine;
+use log::{info, warn};
+use serde::Deserialize;
+use std::io::{Cursor, ErrorKind, Read};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Method, Request, Response};
+
+/// Limits on what the service accepts
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerOptions {
+    /// Requests handled at once; further connections wait to be accepted
+    pub workers: usize,
+    /// Largest request body accepted, in bytes
+    pub max_body_bytes: usize,
+    /// Largest image (width × height) a request may ask for
+    pub max_image_pixels: u64,
+    /// Largest `repair_budget` a request may ask for
+    pub max_repair_budget: u32,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            workers: 4,
+            max_body_bytes: 10 * 1024 * 1024,
+            max_image_pixels: 25_000_000,
+            max_repair_budget: 5,
+        }
+    }
+}
+
+impl ServerOptions {
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    pub fn with_max_body_bytes(mut self, bytes: usize) -> Self {
+        self.max_body_bytes = bytes;
+        self
+    }
+
+    pub fn with_max_image_pixels(mut self, pixels: u64) -> Self {
+        self.max_image_pixels = pixels;
+        self
+    }
+
+    pub fn with_max_repair_budget(mut self, budget: u32) -> Self {
+        self.max_repair_budget = budget;
+        self
+    }
+}
+
+/// Fields a request may set: a preset and the config fields for the look,
+/// placement and background of the code. Everything else, such as the API
+/// key, decoders and preprocessing, stays as the server was configured.
+pub const REQUEST_FIELDS: [&str; 15] = [
+    "preset",
+    "image_width",
+    "image_height",
+    "qr_size_ratio",
+    "qr_position",
+    "dpi",
+    "qr_background_opacity",
+    "dark_color",
+    "light_color",
+    "error_correction",
+    "code_type",
+    "seed",
+    "background_photo",
+    "embed_metadata",
+    "repair_budget",
+];
+
+/// Body of `POST /generate`. Other fields override the config field of the
+/// same name if listed in [`REQUEST_FIELDS`], and `preset` applies a named
+/// preset first.
+#[derive(Debug, Deserialize)]
+struct GenerateRequest {
+    keyword: String,
+    data: String,
+    #[serde(default)]
+    format: ResponseFormat,
+    #[serde(flatten)]
+    overrides: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ResponseFormat {
+    #[default]
+    Png,
+    Jpeg,
+    /// Vector code over the background embedded as a PNG
+    Svg,
+    /// Base64 PNG with the placement, background, validation report and
+    /// repairs made
+    Json,
+}
+
+/// A response before it is written to the connection
+struct Reply {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn new(status: u16, content_type: &'static str, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            content_type,
+            body,
+        }
+    }
+
+    fn json(status: u16, value: serde_json::Value) -> Self {
+        Self::new(status, "application/json", value.to_string().into_bytes())
+    }
+
+    fn error(status: u16, message: impl std::fmt::Display) -> Self {
+        Self::json(status, serde_json::json!({ "error": message.to_string() }))
+    }
+}
+
+/// Local HTTP service exposing generation and decoding:
+///
+/// - `POST /generate`: JSON `{keyword, data, format?, <REQUEST_FIELDS>...}`,
+///   answered with a PNG, a JPEG, an SVG or JSON depending on `format`
+/// - `POST /decode`: an image body, answered with every code found as JSON
+/// - `GET /health`
+pub struct Server {
+    http: tiny_http::Server,
+    generator: QrImageGenerator,
+    options: ServerOptions,
+    stopping: AtomicBool,
+}
+
+impl Server {
+    /// Listen on `address` (e.g. `127.0.0.1:8080`, port 0 for any free port),
+    /// serving requests with `config` as the base configuration
+    pub fn bind(address: &str, config: Config, options: ServerOptions) -> Result<Self> {
+        let generator = QrImageGenerator::new(config)?;
+        if options.workers == 0 {
+            return Err(QrImageError::ConfigError(
+                "Server needs at least one worker".to_string(),
+            ));
+        }
+
+        let http = tiny_http::Server::http(address).map_err(std::io::Error::other)?;
+        Ok(Self {
+            http,
+            generator,
+            options,
+            stopping: AtomicBool::new(false),
+        })
+    }
+
+    /// Address actually bound, useful when binding port 0
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    /// Serve requests on `options.workers` threads until [`shutdown`](Self::shutdown)
+    pub fn run(&self) {
+        info!(
+            "Serving on http://{} with {} worker(s)",
+            self.http.server_addr(),
+            self.options.workers
+        );
+        thread::scope(|scope| {
+            for _ in 0..self.options.workers {
+                scope.spawn(|| {
+                    let mut failures = 0;
+                    loop {
+                        match self.http.recv() {
+                            Ok(request) => {
+                                failures = 0;
+                                self.handle(request);
+                            }
+                            Err(_) if self.stopping.load(Ordering::SeqCst) => break,
+                            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                            Err(e) => {
+                                // Accept errors such as running out of file
+                                // descriptors repeat until something else
+                                // frees up, so wait before trying again
+                                failures += 1;
+                                let delay = accept_backoff(failures);
+                                warn!("Failed to accept request: {}; retrying in {:?}", e, delay);
+                                thread::sleep(delay);
+                            }
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    /// Stop every worker once it finishes its current request
+    pub fn shutdown(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        for _ in 0..self.options.workers {
+            self.http.unblock();
+        }
+    }
+
+    fn handle(&self, mut request: Request) {
+        let started = Instant::now();
+        let method = request.method().clone();
+        let path = request.url().split('?').next().unwrap_or_default().to_string();
+
+        let reply = match (&method, path.as_str()) {
+            (Method::Get, "/health") => Reply::new(200, "text/plain", b"ok".to_vec()),
+            (Method::Post, "/generate") => match self.read_body(&mut request) {
+                Ok(body) => self.generate(&body),
+                Err(reply) => reply,
+            },
+            (Method::Post, "/decode") => match self.read_body(&mut request) {
+                Ok(body) => self.decode(&body),
+                Err(reply) => reply,
+            },
+            (_, "/health" | "/generate" | "/decode") => Reply::error(405, "Method not allowed"),
+            _ => Reply::error(404, "Not found"),
+        };
+
+        info!(
+            "{} {} -> {} ({:.0} ms)",
+            method,
+            path,
+            reply.status,
+            started.elapsed().as_secs_f64() * 1000.0
+        );
+        let content_type = Header::from_bytes("Content-Type", reply.content_type)
+            .expect("static header is valid");
+        let response = Response::from_data(reply.body)
+            .with_status_code(reply.status)
+            .with_header(content_type);
+        if let Err(e) = request.respond(response) {
+            warn!("Failed to send response: {}", e);
+        }
+    }
+
+    /// Read the body, refusing it as soon as it exceeds the size limit
+    fn read_body(&self, request: &mut Request) -> std::result::Result<Vec<u8>, Reply> {
+        let limit = self.options.max_body_bytes;
+        let too_large = || Reply::error(413, format!("Request body exceeds {} bytes", limit));
+        if request.body_length().is_some_and(|length| length > limit) {
+            return Err(too_large());
+        }
+
+        let mut body = Vec::new();
+        request
+            .as_reader()
+            .take(limit as u64 + 1)
+            .read_to_end(&mut body)
+            .map_err(|e| Reply::error(400, format!("Failed to read body: {}", e)))?;
+        if body.len() > limit {
+            return Err(too_large());
+        }
+        Ok(body)
+    }
+
+    fn generate(&self, body: &[u8]) -> Reply {
+        let body: GenerateRequest = match serde_json::from_slice(body) {
+            Ok(body) => body,
+            Err(e) => return Reply::error(400, format!("Invalid request: {}", e)),
+        };
+        let format = match body.format {
+            ResponseFormat::Png | ResponseFormat::Json => OutputFormat::Png,
+            ResponseFormat::Jpeg => OutputFormat::Jpeg,
+            ResponseFormat::Svg => OutputFormat::Svg,
+        };
+        if let Some(field) = body
+            .overrides
+            .keys()
+            .find(|field| !REQUEST_FIELDS.contains(&field.as_str()))
+        {
+            return Reply::error(400, format!("{} cannot be set per request", field));
+        }
+        let repair_budget = body.overrides.get("repair_budget").and_then(|v| v.as_u64());
+        if repair_budget.is_some_and(|budget| budget > self.options.max_repair_budget as u64) {
+            return Reply::error(
+                400,
+                format!("repair_budget is limited to {}", self.options.max_repair_budget),
+            );
+        }
+        let request = GenerationRequest::new(&body.keyword, body.data)
+            .with_format(format)
+            .with_overrides(body.overrides);
+
+        let config = match request.resolve_config(self.generator.config()) {
+            Ok(config) => config,
+            Err(e) => return Reply::error(400, e),
+        };
+        let pixels = config.image_width as u64 * config.image_height as u64;
+        if pixels > self.options.max_image_pixels {
+            return Reply::error(
+                413,
+                format!(
+                    "Image of {}×{} exceeds the limit of {} pixels",
+                    config.image_width, config.image_height, self.options.max_image_pixels
+                ),
+            );
+        }
+
+        let result = match self.generator.generate_request(&request) {
+            Ok(result) => result,
+            Err(e) => return Reply::error(error_status(&e), e),
+        };
+        let encoded = match result.encode() {
+            Ok(encoded) => encoded,
+            Err(e) => return Reply::error(error_status(&e), e),
+        };
+        if body.format != ResponseFormat::Json {
+            return Reply::new(200, format.content_type(), encoded);
+        }
+        Reply::json(
+            200,
+            serde_json::json!({
+                "content_type": format.content_type(),
+                "width": result.image.width(),
+                "height": result.image.height(),
+                "image_base64": base64::engine::general_purpose::STANDARD.encode(&encoded),
+                "placement": result.placement,
+                "background": result.background,
+                "adjustments": result.adjustments,
+                "report": result.report,
+            }),
+        )
+    }
+
+    fn decode(&self, body: &[u8]) -> Reply {
+        // Check the size from the header before decoding allocates the pixels
+        let dimensions = image::io::Reader::new(Cursor::new(body))
+            .with_guessed_format()
+            .map_err(image::ImageError::IoError)
+            .and_then(|reader| reader.into_dimensions());
+        let (width, height) = match dimensions {
+            Ok(dimensions) => dimensions,
+            Err(e) => return Reply::error(400, format!("Unreadable image: {}", e)),
+        };
+        if width as u64 * height as u64 > self.options.max_image_pixels {
+            return Reply::error(413, "Image exceeds the pixel limit");
+        }
+        let image = match image::load_from_memory(body) {
+            Ok(image) => image,
+            Err(e) => return Reply::error(400, format!("Unreadable image: {}", e)),
+        };
+
+        let config = self.generator.config();
+        let validator = QrValidator::new(config.max_validation_attempts)
+            .with_preprocessing(config.preprocessing.clone());
+        let codes = validator.decode_all(&image);
+        let embedded = metadata::read(body);
+        Reply::json(
+            200,
+            serde_json::json!({
+                "codes": codes.iter().map(DecodedCode::to_json).collect::<Vec<_>>(),
+                "metadata": embedded.as_ref().map(ImageMetadata::to_json),
+                "metadata_matches": embedded.as_ref().map(|m| m.matches(&codes)),
+            }),
+        )
+    }
+}
+
+/// Wait before the next `recv` after `failures` accept errors in a row:
+/// doubles from 10 ms up to one second
+fn accept_backoff(failures: u32) -> Duration {
+    Duration::from_millis(10 << failures.saturating_sub(1).min(7)).min(Duration::from_secs(1))
+}
+
+/// HTTP status for a failed generation: the request's fault, the image
+/// source's, or ours
+fn error_status(error: &QrImageError) -> u16 {
+    match error {
+        QrImageError::ConfigError(_)
+        | QrImageError::EncodingError(_)
+        | QrImageError::QrCodeError(_) => 400,
+        QrImageError::QrNotReadable
+        | QrImageError::UnexpectedQrCode { .. }
+        | QrImageError::RobustnessTooLow { .. }
+        | QrImageError::ValidationError(_) => 422,
+        QrImageError::ProviderError(_)
+        | QrImageError::ApiError(_)
+        | QrImageError::RequestError(_) => 502,
+        _ => 500,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output;
+    use image::DynamicImage;
+    use qrcode::QrCode;
+    use std::sync::Arc;
+
+    fn start(options: ServerOptions) -> (Arc<Server>, String) {
+        let server = Arc::new(Server::bind("127.0.0.1:0", Config::default(), options).unwrap());
+        let url = format!("http://{}", server.local_addr().unwrap());
+        let running = Arc::clone(&server);
+        thread::spawn(move || running.run());
+        (server, url)
+    }
+
+    #[test]
+    fn test_routes_and_limits() {
+        let (server, url) = start(ServerOptions::default().with_max_body_bytes(64).with_workers(2));
+        let client = reqwest::blocking::Client::new();
+
+        let health = client.get(format!("{}/health", url)).send().unwrap();
+        assert_eq!(health.status(), 200);
+        assert_eq!(client.get(format!("{}/generate", url)).send().unwrap().status(), 405);
+        assert_eq!(client.get(format!("{}/nope", url)).send().unwrap().status(), 404);
+
+        let post = |body: Vec<u8>| {
+            client
+                .post(format!("{}/generate", url))
+                .body(body)
+                .send()
+                .unwrap()
+        };
+        assert_eq!(post(vec![b' '; 65]).status(), 413);
+        assert_eq!(post(b"{".to_vec()).status(), 400);
+        let zero = post(br#"{"keyword":"sea","data":"x","image_width":0}"#.to_vec());
+        assert_eq!(zero.status(), 400);
+        let error: serde_json::Value = zero.json().unwrap();
+        assert!(error["error"].as_str().unwrap().contains("image dimensions"));
+        for field in [r#""background_dir":"/""#, r#""unsplash_api_key":"k""#, r#""repair_budget":6"#] {
+            let body = format!(r#"{{"keyword":"sea","data":"x",{}}}"#, field);
+            assert_eq!(post(body.into_bytes()).status(), 400, "{}", field);
+        }
+
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_generate_formats() {
+        let (server, url) = start(ServerOptions::default());
+        let client = reqwest::blocking::Client::new();
+        // A seed without an API key draws a placeholder, so no network is used
+        let post = |format: &str, extra: &str| {
+            let body = format!(
+                r#"{{"keyword":"sea","data":"https://example.com","format":"{}","seed":3,"image_width":640,"image_height":480{}}}"#,
+                format, extra
+            );
+            client.post(format!("{}/generate", url)).body(body).send().unwrap()
+        };
+
+        let png = post("png", "");
+        assert_eq!(png.status(), 200);
+        assert_eq!(png.headers()["content-type"], "image/png");
+        let image = image::load_from_memory(&png.bytes().unwrap()).unwrap();
+        assert_eq!((image.width(), image.height()), (640, 480));
+
+        let json: serde_json::Value = post("json", "").json().unwrap();
+        let encoded = json["image_base64"].as_str().unwrap();
+        let bytes = base64::engine::general_purpose::STANDARD.decode(encoded).unwrap();
+        assert!(image::load_from_memory(&bytes).is_ok());
+        assert!(json["report"]["version"].as_u64().is_some());
+        assert_eq!(json["content_type"], "image/png");
+
+        let svg = post("svg", "");
+        assert_eq!(svg.status(), 200);
+        assert_eq!(svg.headers()["content-type"], "image/svg+xml");
+        let svg = svg.text().unwrap();
+        assert!(svg.contains(r#"href="data:image/png;base64,"#));
+        assert!(svg.contains(r##"<path fill="#000000" d="M"##));
+
+        let extra = post("png", r#","max_validation_attempts":1"#);
+        assert_eq!(extra.status(), 400);
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_accept_backoff() {
+        assert_eq!(accept_backoff(1), Duration::from_millis(10));
+        assert_eq!(accept_backoff(2), Duration::from_millis(20));
+        assert_eq!(accept_backoff(50), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_decode_endpoint() {
+        let (server, url) = start(ServerOptions::default());
+        let code = QrCode::new(b"hello").unwrap();
+        let image = DynamicImage::ImageLuma8(code.render::<image::Luma<u8>>().build());
+        let png = output::encode_png(&image, None).unwrap();
+
+        let response = reqwest::blocking::Client::new()
+            .post(format!("{}/decode", url))
+            .body(png.clone())
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let decoded: serde_json::Value = response.json().unwrap();
+        assert_eq!(decoded["codes"][0]["text"], "hello");
+        server.shutdown();
+
+        let (server, url) = start(ServerOptions::default().with_max_image_pixels(100));
+        let response = reqwest::blocking::Client::new()
+            .post(format!("{}/decode", url))
+            .body(png)
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), 413);
+        server.shutdown();
+    }
+}
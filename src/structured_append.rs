@@ -0,0 +1,285 @@
+use crate::error::{QrImageError, Result};
+use crate::symbol_decoder::DecodedSymbol;
+use log::debug;
+use qrcode::bits::Bits;
+use qrcode::canvas::Canvas;
+use qrcode::types::QrError;
+use qrcode::{Color, EcLevel, Version};
+
+/// Most symbols a Structured Append sequence may contain
+pub const MAX_SYMBOLS: usize = 16;
+
+/// Mode indicator, index, total and parity preceding each symbol's data
+const HEADER_BITS: usize = 4 + 4 + 4 + 8;
+
+/// One QR symbol of a Structured Append sequence
+#[derive(Debug, Clone)]
+pub struct AppendedSymbol {
+    /// Position in the sequence, starting at 0
+    pub index: u8,
+    /// Number of symbols in the sequence
+    pub total: u8,
+    /// XOR of every byte of the complete message
+    pub parity: u8,
+    pub version: Version,
+    pub ec_level: EcLevel,
+    /// The slice of the message carried by this symbol
+    pub data: Vec<u8>,
+    width: usize,
+    colors: Vec<Color>,
+}
+
+impl AppendedSymbol {
+    /// Number of modules per side
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Module colours, row by row
+    pub fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+}
+
+/// Structured Append parity: XOR of all message bytes
+pub fn parity(data: &[u8]) -> u8 {
+    data.iter().fold(0, |acc, &b| acc ^ b)
+}
+
+/// Whether the data is too long for a single QR code at this EC level
+pub fn needs_split(data: &[u8], ec_level: EcLevel) -> bool {
+    matches!(
+        qrcode::bits::encode_auto(data, ec_level),
+        Err(QrError::DataTooLong)
+    )
+}
+
+/// Split data across the fewest linked symbols (at most 16) whose version
+/// does not exceed `max_version`
+pub fn split(data: &[u8], ec_level: EcLevel, max_version: i16) -> Result<Vec<AppendedSymbol>> {
+    if data.is_empty() {
+        return Err(QrImageError::EncodingError(
+            "Structured Append needs a non-empty payload".to_string(),
+        ));
+    }
+    if !(1..=40).contains(&max_version) {
+        return Err(QrImageError::EncodingError(format!(
+            "Invalid maximum symbol version {}",
+            max_version
+        )));
+    }
+
+    let capacity = Bits::new(Version::Normal(max_version)).max_len(ec_level)?;
+    let chunk_len = (1..=MAX_SYMBOLS)
+        .map(|count| data.len().div_ceil(count))
+        .find(|&len| required_bits(max_version, len) <= capacity)
+        .ok_or(QrImageError::QrCodeError(QrError::DataTooLong))?;
+
+    let chunks: Vec<&[u8]> = data.chunks(chunk_len).collect();
+    let total = chunks.len() as u8;
+    let parity = parity(data);
+    debug!(
+        "Splitting {} bytes into {} Structured Append symbol(s)",
+        data.len(),
+        total
+    );
+
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| encode_symbol(index as u8, total, parity, chunk, ec_level, max_version))
+        .collect()
+}
+
+/// Reassemble a decoded sequence, checking it is complete and its parity matches
+pub fn reassemble(symbols: &[DecodedSymbol]) -> Result<Vec<u8>> {
+    let headers: Vec<_> = symbols
+        .iter()
+        .map(|symbol| {
+            symbol.structured_append.ok_or_else(|| {
+                QrImageError::ValidationError("Symbol has no Structured Append header".to_string())
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    let first = headers
+        .first()
+        .ok_or_else(|| QrImageError::ValidationError("No symbols to reassemble".to_string()))?;
+
+    let mut pieces: Vec<Option<&[u8]>> = vec![None; first.total as usize];
+    for (symbol, header) in symbols.iter().zip(&headers) {
+        if header.total != first.total || header.parity != first.parity {
+            return Err(QrImageError::ValidationError(
+                "Symbols belong to different Structured Append sequences".to_string(),
+            ));
+        }
+        match pieces.get_mut(header.index as usize) {
+            Some(slot @ None) => *slot = Some(&symbol.data),
+            Some(Some(_)) => {}
+            None => {
+                return Err(QrImageError::ValidationError(format!(
+                    "Symbol index {} is outside a sequence of {}",
+                    header.index, header.total
+                )))
+            }
+        }
+    }
+
+    let mut data = Vec::new();
+    for (index, piece) in pieces.iter().enumerate() {
+        let piece = piece.ok_or_else(|| {
+            QrImageError::ValidationError(format!(
+                "Symbol {} of {} is missing",
+                index + 1,
+                first.total
+            ))
+        })?;
+        data.extend_from_slice(piece);
+    }
+
+    if parity(&data) != first.parity {
+        return Err(QrImageError::ValidationError(
+            "Structured Append parity does not match reassembled data".to_string(),
+        ));
+    }
+
+    Ok(data)
+}
+
+fn byte_count_bits(version: i16) -> usize {
+    if version <= 9 {
+        8
+    } else {
+        16
+    }
+}
+
+fn required_bits(version: i16, data_len: usize) -> usize {
+    HEADER_BITS + 4 + byte_count_bits(version) + data_len * 8
+}
+
+fn encode_symbol(
+    index: u8,
+    total: u8,
+    parity: u8,
+    chunk: &[u8],
+    ec_level: EcLevel,
+    max_version: i16,
+) -> Result<AppendedSymbol> {
+    for v in 1..=max_version {
+        let version = Version::Normal(v);
+        let capacity = Bits::new(version).max_len(ec_level)?;
+        if required_bits(v, chunk.len()) > capacity {
+            continue;
+        }
+
+        let mut bits = BitWriter::default();
+        bits.push(0b0011, 4);
+        bits.push(index as usize, 4);
+        bits.push(total as usize - 1, 4);
+        bits.push(parity as usize, 8);
+        bits.push(0b0100, 4);
+        bits.push(chunk.len(), byte_count_bits(v));
+        for &byte in chunk {
+            bits.push(byte as usize, 8);
+        }
+        let codewords = bits.finish(capacity);
+
+        let (data, ec) = qrcode::ec::construct_codewords(&codewords, version, ec_level)?;
+        let mut canvas = Canvas::new(version, ec_level);
+        canvas.draw_all_functional_patterns();
+        canvas.draw_data(&data, &ec);
+        let colors = canvas.apply_best_mask().into_colors();
+
+        return Ok(AppendedSymbol {
+            index,
+            total,
+            parity,
+            version,
+            ec_level,
+            data: chunk.to_vec(),
+            width: version.width() as usize,
+            colors,
+        });
+    }
+
+    Err(QrError::DataTooLong.into())
+}
+
+/// Big-endian bit accumulator; `qrcode::bits::Bits` cannot emit the
+/// Structured Append header fields
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl BitWriter {
+    fn push(&mut self, value: usize, count: usize) {
+        for i in (0..count).rev() {
+            if self.len.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            if (value >> i) & 1 == 1 {
+                let last = self.bytes.len() - 1;
+                self.bytes[last] |= 0x80 >> (self.len % 8);
+            }
+            self.len += 1;
+        }
+    }
+
+    /// Add the terminator and pad codewords up to `capacity` bits
+    fn finish(mut self, capacity: usize) -> Vec<u8> {
+        self.push(0, 4.min(capacity - self.len));
+        self.len = self.bytes.len() * 8;
+        for pad in [0xec, 0x11].iter().cycle() {
+            if self.bytes.len() * 8 >= capacity {
+                break;
+            }
+            self.bytes.push(*pad);
+        }
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbol_decoder;
+
+    fn decode(symbol: &AppendedSymbol) -> DecodedSymbol {
+        let width = symbol.width();
+        symbol_decoder::decode_modules(width, |x, y| symbol.colors()[y * width + x] == Color::Dark)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_split_and_reassemble() {
+        let data: Vec<u8> = (0..5000u32).map(|i| (i * 7 % 251) as u8).collect();
+        assert!(needs_split(&data, EcLevel::M));
+
+        let symbols = split(&data, EcLevel::M, 40).unwrap();
+        assert_eq!(symbols.len(), 3);
+        assert!(symbols.iter().all(|s| s.total == 3 && s.parity == parity(&data)));
+
+        let decoded: Vec<DecodedSymbol> = symbols.iter().rev().map(decode).collect();
+        assert_eq!(decoded[0].structured_append.unwrap().index, 2);
+        assert_eq!(reassemble(&decoded).unwrap(), data);
+    }
+
+    #[test]
+    fn test_reassemble_detects_missing_symbol() {
+        let data = vec![b'a'; 300];
+        let symbols = split(&data, EcLevel::L, 5).unwrap();
+        assert!(symbols.len() > 2);
+
+        let decoded: Vec<DecodedSymbol> = symbols.iter().skip(1).map(decode).collect();
+        assert!(reassemble(&decoded).is_err());
+    }
+
+    #[test]
+    fn test_split_rejects_oversized_payload() {
+        let data = vec![0u8; 3000 * MAX_SYMBOLS];
+        assert!(split(&data, EcLevel::L, 40).is_err());
+    }
+}
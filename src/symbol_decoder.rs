@@ -0,0 +1,602 @@
+//! Minimal QR symbol decoder for sampled module grids.
+//!
+//! rqrr finds and samples codes well, but its payload parser rejects the
+//! Structured Append mode indicator outright. This decoder works on the
+//! module grid rqrr sampled and understands the full header, so linked
+//! symbols can be verified and reassembled.
+
+use crate::error::{QrImageError, Result};
+use qrcode::canvas::{is_functional, Canvas, MaskPattern};
+use qrcode::{Color, EcLevel, Version};
+use serde::{Deserialize, Serialize};
+
+/// Structured Append header carried at the start of a linked symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StructuredAppendHeader {
+    /// Position of this symbol in the sequence, starting at 0
+    pub index: u8,
+    /// Number of symbols in the sequence
+    pub total: u8,
+    /// XOR of every byte of the complete message
+    pub parity: u8,
+}
+
+/// Payload and header information read from one symbol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedSymbol {
+    pub version: i16,
+    pub ec_level: EcLevel,
+    pub mask: u8,
+    pub structured_append: Option<StructuredAppendHeader>,
+    /// Decoded bytes, without mode headers (Kanji is returned as Shift JIS)
+    pub data: Vec<u8>,
+    /// Number of codewords repaired by error correction
+    pub corrected_codewords: usize,
+}
+
+/// Decode a sampled module grid; `dark(x, y)` reports whether a module is dark
+pub fn decode_modules<F>(size: usize, dark: F) -> Result<DecodedSymbol>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    if size < 21 || !(size - 17).is_multiple_of(4) || size > 177 {
+        return Err(decode_error(format!("Invalid symbol size {}", size)));
+    }
+    let version = ((size - 17) / 4) as i16;
+
+    let (ec_level, mask) = read_format(size, &dark)?;
+    let raw = read_codewords(Version::Normal(version), size, mask, &dark);
+    let (data, corrected_codewords) = correct_codewords(&raw, Version::Normal(version), ec_level)?;
+    let (structured_append, data) = parse_segments(&data, version)?;
+
+    Ok(DecodedSymbol {
+        version,
+        ec_level,
+        mask,
+        structured_append,
+        data,
+        corrected_codewords,
+    })
+}
+
+/// Module colours the symbol was encoded with, rebuilt from its
+/// error-corrected codewords so damaged modules come out as intended.
+/// Row-major, `true` for dark.
+pub fn ideal_modules<F>(size: usize, dark: F) -> Result<Vec<bool>>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    if size < 21 || !(size - 17).is_multiple_of(4) || size > 177 {
+        return Err(decode_error(format!("Invalid symbol size {}", size)));
+    }
+    let version = Version::Normal(((size - 17) / 4) as i16);
+
+    let (ec_level, mask) = read_format(size, &dark)?;
+    let raw = read_codewords(version, size, mask, &dark);
+    let (data, _) = correct_codewords(&raw, version, ec_level)?;
+    let (data, ec) = qrcode::ec::construct_codewords(&data, version, ec_level)?;
+
+    let mut canvas = Canvas::new(version, ec_level);
+    canvas.draw_all_functional_patterns();
+    canvas.draw_data(&data, &ec);
+    canvas.apply_mask(MASK_PATTERNS[mask as usize]);
+    Ok(canvas
+        .into_colors()
+        .into_iter()
+        .map(|color| color == Color::Dark)
+        .collect())
+}
+
+/// Mask patterns indexed by their three-bit reference
+const MASK_PATTERNS: [MaskPattern; 8] = [
+    MaskPattern::Checkerboard,
+    MaskPattern::HorizontalLines,
+    MaskPattern::VerticalLines,
+    MaskPattern::DiagonalLines,
+    MaskPattern::LargeCheckerboard,
+    MaskPattern::Fields,
+    MaskPattern::Diamonds,
+    MaskPattern::Meadow,
+];
+
+fn decode_error(message: String) -> QrImageError {
+    QrImageError::ValidationError(message)
+}
+
+// ---------------------------------------------------------------------------
+// Format information
+
+const FORMAT_XS: [usize; 15] = [8, 8, 8, 8, 8, 8, 8, 8, 7, 5, 4, 3, 2, 1, 0];
+const FORMAT_YS: [usize; 15] = [0, 1, 2, 3, 4, 5, 7, 8, 8, 8, 8, 8, 8, 8, 8];
+const FORMAT_MASK: u16 = 0x5412;
+
+fn format_codeword(data: u16) -> u16 {
+    let mut rem = data << 10;
+    for bit in (10..15).rev() {
+        if rem & (1 << bit) != 0 {
+            rem ^= 0x537 << (bit - 10);
+        }
+    }
+    ((data << 10) | rem) ^ FORMAT_MASK
+}
+
+fn read_format<F>(size: usize, dark: &F) -> Result<(EcLevel, u8)>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let mut main = 0u16;
+    let mut side = 0u16;
+    for i in (0..15).rev() {
+        main = (main << 1) | dark(FORMAT_XS[i], FORMAT_YS[i]) as u16;
+    }
+    for i in 0..7 {
+        side = (side << 1) | dark(8, size - 1 - i) as u16;
+    }
+    for i in 0..8 {
+        side = (side << 1) | dark(size - 8 + i, 8) as u16;
+    }
+
+    // Pick the valid format word nearest to either copy; up to 3 bit errors
+    // are correctable
+    let (distance, data) = (0u16..32)
+        .map(|data| {
+            let word = format_codeword(data);
+            let distance = (word ^ main).count_ones().min((word ^ side).count_ones());
+            (distance, data)
+        })
+        .min()
+        .unwrap_or((u32::MAX, 0));
+
+    if distance > 3 {
+        return Err(decode_error("Format information unreadable".to_string()));
+    }
+
+    let ec_level = match data >> 3 {
+        0b01 => EcLevel::L,
+        0b00 => EcLevel::M,
+        0b11 => EcLevel::Q,
+        _ => EcLevel::H,
+    };
+    Ok((ec_level, (data & 7) as u8))
+}
+
+// ---------------------------------------------------------------------------
+// Codeword placement
+
+fn is_reserved(version: Version, size: usize, x: usize, y: usize) -> bool {
+    let Version::Normal(v) = version else {
+        return true;
+    };
+
+    // Version information blocks beside the top-right and bottom-left finders
+    let version_info = v >= 7 && ((y < 6 && x + 11 >= size) || (x < 6 && y + 11 >= size));
+    version_info || is_functional(version, size as i16, x as i16, y as i16)
+}
+
+fn mask_bit(mask: u8, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (y + x).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (y + x).is_multiple_of(3),
+        4 => ((y / 2) + (x / 3)).is_multiple_of(2),
+        5 => (y * x) % 2 + (y * x) % 3 == 0,
+        6 => ((y * x) % 2 + (y * x) % 3).is_multiple_of(2),
+        _ => ((y * x) % 3 + (y + x) % 2).is_multiple_of(2),
+    }
+}
+
+fn read_codewords<F>(version: Version, size: usize, mask: u8, dark: &F) -> Vec<u8>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let mut bits = Vec::new();
+    let mut x = size - 1;
+    let mut upward = true;
+
+    loop {
+        if x == 6 {
+            x -= 1;
+        }
+        for step in 0..size {
+            let y = if upward { size - 1 - step } else { step };
+            for col in [x, x - 1] {
+                if !is_reserved(version, size, col, y) {
+                    bits.push(dark(col, y) ^ mask_bit(mask, col, y));
+                }
+            }
+        }
+        if x < 2 {
+            break;
+        }
+        x -= 2;
+        upward = !upward;
+    }
+
+    bits.chunks_exact(8)
+        .map(|byte| byte.iter().fold(0u8, |acc, &bit| (acc << 1) | bit as u8))
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Block structure and error correction
+
+/// Data block lengths and EC codewords per block, recovered from the
+/// encoder's interleaving so no capacity table has to be duplicated here
+fn block_layout(version: Version, ec_level: EcLevel) -> Result<(Vec<usize>, Vec<usize>, usize)> {
+    let data_len = qrcode::bits::Bits::new(version).max_len(ec_level)? / 8;
+
+    let low: Vec<u8> = (0..data_len).map(|i| i as u8).collect();
+    let high: Vec<u8> = (0..data_len).map(|i| (i >> 8) as u8).collect();
+    let (order_low, ec) = qrcode::ec::construct_codewords(&low, version, ec_level)?;
+    let (order_high, _) = qrcode::ec::construct_codewords(&high, version, ec_level)?;
+
+    // Original data index stored at each interleaved position
+    let order: Vec<usize> = order_low
+        .iter()
+        .zip(&order_high)
+        .map(|(&lo, &hi)| (hi as usize) << 8 | lo as usize)
+        .collect();
+
+    // Interleaving starts with the first codeword of every block
+    let blocks = order.iter().position(|&i| i == 1).unwrap_or(1);
+    let mut lengths: Vec<usize> = order[..blocks].windows(2).map(|w| w[1] - w[0]).collect();
+    lengths.push(data_len - order[blocks - 1]);
+
+    Ok((order, lengths, ec.len() / blocks))
+}
+
+fn correct_codewords(raw: &[u8], version: Version, ec_level: EcLevel) -> Result<(Vec<u8>, usize)> {
+    let (order, lengths, ec_per_block) = block_layout(version, ec_level)?;
+    let data_len = order.len();
+    let blocks = lengths.len();
+
+    if raw.len() < data_len + ec_per_block * blocks {
+        return Err(decode_error("Symbol has too few codewords".to_string()));
+    }
+
+    let mut data = vec![0u8; data_len];
+    for (position, &index) in order.iter().enumerate() {
+        data[index] = raw[position];
+    }
+
+    let mut corrected = 0;
+    let mut start = 0;
+    for (block, &len) in lengths.iter().enumerate() {
+        let mut codeword: Vec<u8> = data[start..start + len].to_vec();
+        codeword.extend((0..ec_per_block).map(|i| raw[data_len + i * blocks + block]));
+
+        corrected += reed_solomon::correct(&mut codeword, ec_per_block)
+            .ok_or_else(|| decode_error("Too many errors to correct".to_string()))?;
+        data[start..start + len].copy_from_slice(&codeword[..len]);
+        start += len;
+    }
+
+    Ok((data, corrected))
+}
+
+mod reed_solomon {
+    //! Reed-Solomon decoding over GF(256) with the QR primitive polynomial
+
+    struct Field {
+        exp: [u8; 512],
+        log: [u8; 256],
+    }
+
+    impl Field {
+        fn new() -> Self {
+            let mut exp = [0u8; 512];
+            let mut log = [0u8; 256];
+            let mut x: u16 = 1;
+            for (i, slot) in exp.iter_mut().take(255).enumerate() {
+                *slot = x as u8;
+                log[x as usize] = i as u8;
+                x <<= 1;
+                if x & 0x100 != 0 {
+                    x ^= 0x11d;
+                }
+            }
+            for i in 255..512 {
+                exp[i] = exp[i - 255];
+            }
+            Self { exp, log }
+        }
+
+        fn mul(&self, a: u8, b: u8) -> u8 {
+            if a == 0 || b == 0 {
+                0
+            } else {
+                self.exp[self.log[a as usize] as usize + self.log[b as usize] as usize]
+            }
+        }
+
+        fn div(&self, a: u8, b: u8) -> u8 {
+            if a == 0 {
+                0
+            } else {
+                self.exp[(self.log[a as usize] as usize + 255 - self.log[b as usize] as usize) % 255]
+            }
+        }
+
+        fn pow(&self, power: usize) -> u8 {
+            self.exp[power % 255]
+        }
+
+        /// Evaluate a polynomial given lowest degree first
+        fn eval(&self, poly: &[u8], x: u8) -> u8 {
+            poly.iter().rev().fold(0, |acc, &c| self.mul(acc, x) ^ c)
+        }
+    }
+
+    /// Correct `codeword` (data followed by `ec_len` EC bytes, highest degree
+    /// first) in place. Returns the number of corrected bytes, or `None` if
+    /// the errors exceed the code's capacity.
+    pub fn correct(codeword: &mut [u8], ec_len: usize) -> Option<usize> {
+        let gf = Field::new();
+        let n = codeword.len();
+
+        let syndromes: Vec<u8> = (0..ec_len)
+            .map(|j| {
+                codeword
+                    .iter()
+                    .fold(0, |acc, &c| gf.mul(acc, gf.pow(j)) ^ c)
+            })
+            .collect();
+        if syndromes.iter().all(|&s| s == 0) {
+            return Some(0);
+        }
+
+        // Berlekamp-Massey: error locator, lowest degree first
+        let mut locator = vec![1u8];
+        let mut previous = vec![1u8];
+        let mut errors = 0;
+        let mut shift = 1;
+        let mut last_discrepancy = 1u8;
+        for step in 0..ec_len {
+            let mut discrepancy = syndromes[step];
+            for i in 1..=errors {
+                discrepancy ^= gf.mul(locator.get(i).copied().unwrap_or(0), syndromes[step - i]);
+            }
+            if discrepancy == 0 {
+                shift += 1;
+                continue;
+            }
+
+            let scale = gf.div(discrepancy, last_discrepancy);
+            let mut next = locator.clone();
+            next.resize(next.len().max(previous.len() + shift), 0);
+            for (i, &c) in previous.iter().enumerate() {
+                next[i + shift] ^= gf.mul(scale, c);
+            }
+
+            if 2 * errors <= step {
+                previous = std::mem::replace(&mut locator, next);
+                errors = step + 1 - errors;
+                last_discrepancy = discrepancy;
+                shift = 1;
+            } else {
+                locator = next;
+                shift += 1;
+            }
+        }
+        if 2 * errors > ec_len {
+            return None;
+        }
+
+        // Chien search: an error at degree d makes locator(α^-d) vanish
+        let degrees: Vec<usize> = (0..n)
+            .filter(|&d| gf.eval(&locator, gf.pow(255 - d % 255)) == 0)
+            .collect();
+        if degrees.len() != errors {
+            return None;
+        }
+
+        // Forney: magnitude = X * Ω(X⁻¹) / Λ'(X⁻¹), with Ω = S·Λ mod x^ec_len
+        let mut evaluator = vec![0u8; ec_len];
+        for (i, &s) in syndromes.iter().enumerate() {
+            for (j, &l) in locator.iter().enumerate() {
+                if i + j < ec_len {
+                    evaluator[i + j] ^= gf.mul(s, l);
+                }
+            }
+        }
+        let derivative: Vec<u8> = locator
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(i, &c)| if i % 2 == 1 { c } else { 0 })
+            .collect();
+
+        for &degree in &degrees {
+            let x = gf.pow(degree);
+            let x_inv = gf.pow(255 - degree % 255);
+            let denominator = gf.eval(&derivative, x_inv);
+            if denominator == 0 {
+                return None;
+            }
+            let magnitude = gf.mul(x, gf.div(gf.eval(&evaluator, x_inv), denominator));
+            codeword[n - 1 - degree] ^= magnitude;
+        }
+
+        Some(degrees.len())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Segment parsing
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.position
+    }
+
+    fn take(&mut self, count: usize) -> Result<usize> {
+        if count > self.remaining() {
+            return Err(decode_error("Segment data ends unexpectedly".to_string()));
+        }
+        let mut value = 0;
+        for _ in 0..count {
+            let bit = self.data[self.position / 8] >> (7 - self.position % 8) & 1;
+            value = (value << 1) | bit as usize;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+}
+
+const ALPHANUMERIC: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+fn count_bits(version: i16, short: usize, medium: usize, long: usize) -> usize {
+    match version {
+        1..=9 => short,
+        10..=26 => medium,
+        _ => long,
+    }
+}
+
+fn parse_segments(data: &[u8], version: i16) -> Result<(Option<StructuredAppendHeader>, Vec<u8>)> {
+    let mut reader = BitReader { data, position: 0 };
+    let mut header = None;
+    let mut output = Vec::new();
+
+    while reader.remaining() >= 4 {
+        match reader.take(4)? {
+            0b0000 => break,
+            0b0011 => {
+                header = Some(StructuredAppendHeader {
+                    index: reader.take(4)? as u8,
+                    total: reader.take(4)? as u8 + 1,
+                    parity: reader.take(8)? as u8,
+                });
+            }
+            0b0001 => {
+                let mut count = reader.take(count_bits(version, 10, 12, 14))?;
+                while count > 0 {
+                    let digits = count.min(3);
+                    let value = reader.take([4, 7, 10][digits - 1])?;
+                    output.extend(format!("{:0width$}", value, width = digits).bytes());
+                    count -= digits;
+                }
+            }
+            0b0010 => {
+                let mut count = reader.take(count_bits(version, 9, 11, 13))?;
+                while count >= 2 {
+                    let value = reader.take(11)?;
+                    output.push(ALPHANUMERIC[(value / 45) % 45]);
+                    output.push(ALPHANUMERIC[value % 45]);
+                    count -= 2;
+                }
+                if count == 1 {
+                    output.push(ALPHANUMERIC[reader.take(6)? % 45]);
+                }
+            }
+            0b0100 => {
+                let count = reader.take(count_bits(version, 8, 16, 16))?;
+                for _ in 0..count {
+                    output.push(reader.take(8)? as u8);
+                }
+            }
+            0b1000 => {
+                let count = reader.take(count_bits(version, 8, 10, 12))?;
+                for _ in 0..count {
+                    let value = reader.take(13)?;
+                    let packed = ((value / 0xc0) << 8) | (value % 0xc0);
+                    let sjis = if packed + 0x8140 <= 0x9ffc {
+                        packed + 0x8140
+                    } else {
+                        packed + 0xc140
+                    };
+                    output.extend([(sjis >> 8) as u8, sjis as u8]);
+                }
+            }
+            0b0111 => {
+                // ECI designators only change interpretation; skip 1-3 bytes
+                let first = reader.take(8)?;
+                if first & 0xc0 == 0x80 {
+                    reader.take(8)?;
+                } else if first & 0xe0 == 0xc0 {
+                    reader.take(16)?;
+                }
+            }
+            0b0101 => {}
+            0b1001 => {
+                reader.take(8)?;
+            }
+            mode => return Err(decode_error(format!("Unknown segment mode {:04b}", mode))),
+        }
+    }
+
+    Ok((header, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qrcode::QrCode;
+
+    fn decode_code(code: &QrCode) -> DecodedSymbol {
+        let colors = code.to_colors();
+        let width = code.width();
+        decode_modules(width, |x, y| colors[y * width + x] == qrcode::Color::Dark).unwrap()
+    }
+
+    #[test]
+    fn test_decode_matches_encoder() {
+        for (data, ec_level) in [
+            (&b"https://example.com"[..], EcLevel::M),
+            (&b"0123456789012345"[..], EcLevel::H),
+            (&[b'x'; 700][..], EcLevel::Q),
+        ] {
+            let code = QrCode::with_error_correction_level(data, ec_level).unwrap();
+            let decoded = decode_code(&code);
+            assert_eq!(decoded.data, data);
+            assert_eq!(decoded.ec_level, ec_level);
+            assert_eq!(decoded.structured_append, None);
+        }
+    }
+
+    #[test]
+    fn test_ideal_modules_undo_damage() {
+        let code = QrCode::with_error_correction_level(b"https://example.com/ideal", EcLevel::Q)
+            .unwrap();
+        let width = code.width();
+        let original: Vec<bool> = code
+            .to_colors()
+            .iter()
+            .map(|&color| color == qrcode::Color::Dark)
+            .collect();
+
+        let mut damaged = original.clone();
+        for index in [width * 12 + 12, width * 14 + 15, width * 20 + 10] {
+            damaged[index] = !damaged[index];
+        }
+        let ideal = ideal_modules(width, |x, y| damaged[y * width + x]).unwrap();
+        assert_eq!(ideal, original);
+    }
+
+    #[test]
+    fn test_reed_solomon_corrects_errors() {
+        let data = b"structured append".to_vec();
+        let ec = qrcode::ec::create_error_correction_code(&data, 10);
+        let mut codeword = [data.clone(), ec].concat();
+        codeword[0] ^= 0x5a;
+        codeword[7] ^= 0xff;
+        codeword[20] ^= 0x01;
+
+        assert_eq!(reed_solomon::correct(&mut codeword, 10), Some(3));
+        assert_eq!(&codeword[..data.len()], &data[..]);
+
+        codeword[1] ^= 1;
+        codeword[2] ^= 1;
+        codeword[3] ^= 1;
+        codeword[4] ^= 1;
+        codeword[5] ^= 1;
+        codeword[6] ^= 1;
+        assert_eq!(reed_solomon::correct(&mut codeword, 10), None);
+    }
+}